@@ -0,0 +1,61 @@
+//! Compares how fast an output-heavy program runs when every byte of output is flushed on its own,
+//! as the interpreter used to, and when output stays buffered until it's needed.
+//!
+//! Run with `cargo bench --bench output`.
+
+use brainstorm::interpreter::{EofBehaviour, Interpreter};
+use brainstorm::parser::{Lints, Program};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+/// Writes 'A' 1,000,000 times
+const SOURCE: &str = "++++++++[>++++++++<-]>+>++++++++++[>++++++++++[>++++++++++[>++++++++++[>++++++++++[>++++++++++[<<<<<<.>>>>>>-]<-]<-]<-]<-]<-]";
+
+/// How many times each way of writing output is timed, keeping the fastest run
+const RUNS: usize = 5;
+
+fn time_run(program: &Program, output: impl Write, buffered_output: bool) -> Duration {
+    let mut interpreter = Interpreter::new(
+        program.clone(),
+        16,
+        0,
+        EofBehaviour::DontSet,
+        std::io::empty(),
+        output,
+    );
+    interpreter.set_buffered_output(buffered_output);
+    let started = Instant::now();
+    interpreter.run().expect("the benchmark program failed");
+    started.elapsed()
+}
+
+fn fastest(mut run: impl FnMut() -> Duration) -> Duration {
+    (0..RUNS).map(|_| run()).min().unwrap()
+}
+
+fn main() {
+    let program = Program::parse(
+        BufReader::new(SOURCE.as_bytes()),
+        false,
+        Lints::default(),
+        None,
+    )
+    .unwrap();
+    let path = std::env::temp_dir().join("brainstorm-output-bench");
+    let file = || File::create(&path).expect("failed to create the output file");
+
+    let unbuffered = fastest(|| time_run(&program, file(), false));
+    let buffered = fastest(|| time_run(&program, BufWriter::new(file()), true));
+    let discarded = fastest(|| time_run(&program, std::io::sink(), true));
+    let _ = std::fs::remove_file(&path);
+
+    println!("1,000,000 bytes of output, fastest of {RUNS} runs:");
+    println!("  flushed after every byte: {unbuffered:>10.3?}");
+    println!("  buffered:                 {buffered:>10.3?}");
+    println!("  discarded:                {discarded:>10.3?}");
+    println!(
+        "  buffering is {:.1}x faster",
+        unbuffered.as_secs_f64() / buffered.as_secs_f64()
+    );
+}
@@ -1,12 +1,23 @@
+pub mod asciicast;
+pub mod assertions;
 pub mod debugger;
+pub mod fixture;
+pub mod options;
+pub mod repro;
+pub mod snapshot;
+pub mod transcript;
 
+use crate::parser::ast::Node;
 use crate::parser::{Program, Token};
 use clap::ValueEnum;
 use owo_colors::{OwoColorize, Style};
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Display;
-use std::fmt::Write;
-use std::io::{Read, Write as _};
+use std::fmt::Write as _;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::ops::{Range, RangeInclusive};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,6 +28,26 @@ pub enum InterpreterError {
     InvalidProgram,
     #[error("Failed to read input")]
     InputError,
+    #[error("Failed to write output")]
+    OutputError,
+    #[error("No input is available yet")]
+    InputWouldBlock,
+    #[error("Output limit of {0} bytes exceeded")]
+    OutputLimitExceeded(u64),
+    #[error("No output for {0} steps")]
+    OutputWatchdog(u64),
+}
+
+/// The outcome of a single `Interpreter::try_step`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StepOutcome {
+    /// The instruction was executed, and there's still more program to execute
+    Continue,
+    /// The program has halted (reached EOF)
+    Halted,
+    /// The current instruction is an input, but no input is available yet. Nothing was executed,
+    /// so the step can be retried once more input arrives
+    NeedInput,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
@@ -24,6 +55,9 @@ pub enum EofBehaviour {
     SetZero,
     SetMinusOne,
     DontSet,
+    /// Sets the cell to a specific value. Only available at runtime, through the debugger
+    #[value(skip)]
+    SetValue(u8),
 }
 
 impl Display for EofBehaviour {
@@ -32,83 +66,1020 @@ impl Display for EofBehaviour {
             Self::SetZero => write!(f, "set-zero"),
             Self::SetMinusOne => write!(f, "set-minus-one"),
             Self::DontSet => write!(f, "dont-set"),
+            Self::SetValue(value) => write!(f, "set {value:#04x}"),
+        }
+    }
+}
+
+/// How `.` turns the cell under the pointer into output bytes, which matters for cells wider than
+/// a byte
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum WideOutput {
+    /// Writes only the cell's lowest byte, as classic brainfuck does
+    #[default]
+    LowByte,
+    /// Writes every byte of the cell, in the configured byte order
+    AllBytes,
+    /// Treats the cell as a Unicode code point and writes it encoded as UTF-8
+    Unicode,
+}
+
+/// The order in which the bytes of a multi-byte value are written
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+/// How many bits a tape cell holds. Arithmetic wraps around at the cell's width.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum CellWidth {
+    #[default]
+    #[value(name = "8")]
+    Bits8,
+    #[value(name = "16")]
+    Bits16,
+    #[value(name = "32")]
+    Bits32,
+}
+
+impl CellWidth {
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::Bits8 => 8,
+            Self::Bits16 => 16,
+            Self::Bits32 => 32,
+        }
+    }
+
+    /// Returns the largest value a cell can hold, which is also the mask values wrap around with
+    pub fn max(self) -> u32 {
+        u32::MAX >> (32 - self.bits())
+    }
+
+    /// Returns how many hex digits it takes to show any value of a cell
+    pub fn hex_digits(self) -> usize {
+        self.bits() as usize / 4
+    }
+
+    /// Reads a cell's value as a two's complement signed number
+    pub fn signed(self, value: u32) -> i32 {
+        let shift = 32 - self.bits();
+        ((value << shift) as i32) >> shift
+    }
+}
+
+impl Display for CellWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.bits())
+    }
+}
+
+/// A breakpoint on a program address
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub enabled: bool,
+    /// Amount of upcoming hits to skip before the breakpoint stops execution
+    pub ignore_count: u64,
+}
+
+/// A comparison between a tape cell and a value
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+}
+
+impl Comparison {
+    /// Returns whether a cell holding `cell` satisfies the comparison with `value`
+    pub fn holds(self, cell: u32, value: u32) -> bool {
+        match self {
+            Self::Equal => cell == value,
+            Self::NotEqual => cell != value,
+            Self::Less => cell < value,
+            Self::Greater => cell > value,
+        }
+    }
+
+    /// Returns the fewest increments or decrements that would make a cell holding `cell` satisfy
+    /// the comparison with `value`, which is 0 if it already does. Cells wrap around after `max`,
+    /// so `==` can be reached in either direction, while `<` and `>` can only be approached
+    /// without wrapping. Returns None if the comparison can never hold, such as `< 0`.
+    pub fn distance(self, cell: u32, value: u32, max: u32) -> Option<u32> {
+        match self {
+            _ if self.holds(cell, value) => Some(0),
+            Self::Equal => {
+                Some((cell.wrapping_sub(value) & max).min(value.wrapping_sub(cell) & max))
+            }
+            Self::NotEqual => Some(1),
+            Self::Less => (value > 0).then(|| cell - value + 1),
+            Self::Greater => (value < max).then(|| value - cell + 1),
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Equal => write!(f, "=="),
+            Self::NotEqual => write!(f, "!="),
+            Self::Less => write!(f, "<"),
+            Self::Greater => write!(f, ">"),
         }
     }
 }
 
-pub struct Interpreter<R: Read> {
-    tape: Vec<u8>,
+/// A breakpoint on the tape's contents, which stops execution the moment the cell at `address`
+/// starts satisfying the comparison with `value`
+#[derive(Debug, Clone)]
+pub struct DataBreakpoint {
+    pub address: usize,
+    pub comparison: Comparison,
+    pub value: u32,
+    /// Whether the comparison held after the last step, so only changes trigger the breakpoint
+    satisfied: bool,
+}
+
+impl Display for DataBreakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:#x} {} {:#04x}",
+            self.address, self.comparison, self.value
+        )
+    }
+}
+
+/// A named watch over a region of cells read as a little-endian integer, which stops execution
+/// whenever that integer changes
+#[derive(Debug, Clone)]
+pub struct WatchExpression {
+    pub name: String,
+    pub address: usize,
+    /// The amount of cells in the region, from 1 to 8
+    pub len: usize,
+    /// The region's value after the last step
+    pub value: u64,
+}
+
+impl Display for WatchExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:#x}:{}) = {}",
+            self.name, self.address, self.len, self.value
+        )
+    }
+}
+
+/// A layout for reading a region of cells as an unsigned fixed-point number, with `int_cells`
+/// cells of integer part followed by `frac_cells` cells of fraction, each a digit in the base of
+/// the cell's range (256 for 8-bit cells). The cells are big-endian: the most significant one is at
+/// the lowest address, so the number reads in the same order as the tape.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FixedPoint {
+    pub int_cells: usize,
+    pub frac_cells: usize,
+}
+
+impl FixedPoint {
+    /// The most cells a fixed-point number may span
+    pub const MAX_CELLS: usize = 8;
+    /// The most bits a fixed-point number may span, which limits how many wide cells it can span
+    pub const MAX_BITS: u32 = 64;
+
+    /// Parses a layout written as `<int-cells>.<frac-cells>`, such as `2.1`
+    pub fn parse(s: &str) -> Result<FixedPoint, String> {
+        let (int_cells, frac_cells) = s
+            .split_once('.')
+            .ok_or("expected <int-cells>.<frac-cells>, such as 2.1")?;
+        let layout = FixedPoint {
+            int_cells: int_cells.parse().map_err(|e| format!("{e}"))?,
+            frac_cells: frac_cells.parse().map_err(|e| format!("{e}"))?,
+        };
+        if !(1..=Self::MAX_CELLS).contains(&layout.cells()) {
+            return Err(format!(
+                "a fixed-point number spans 1 to {} cells",
+                Self::MAX_CELLS
+            ));
+        }
+        Ok(layout)
+    }
+
+    /// Returns how many cells a number spans
+    pub fn cells(&self) -> usize {
+        self.int_cells + self.frac_cells
+    }
+
+    /// Formats the number held in `cells`, which must be `cells()` long and each `bits` wide, as an
+    /// exact decimal. The cells must span at most `MAX_BITS`.
+    pub fn format(&self, cells: &[u32], bits: u32) -> String {
+        let read = |cells: &[u32]| {
+            cells
+                .iter()
+                .fold(0u128, |value, &cell| value << bits | cell as u128)
+        };
+        let (int_part, frac_part) = cells.split_at(self.int_cells);
+        let mut result = read(int_part).to_string();
+
+        // Every fraction bit adds at most one decimal digit
+        let scale = 1u128 << (bits as usize * self.frac_cells);
+        let mut frac = read(frac_part);
+        if frac != 0 {
+            result.push('.');
+            while frac != 0 {
+                frac *= 10;
+                result.push(char::from(b'0' + (frac / scale) as u8));
+                frac %= scale;
+            }
+        }
+        result
+    }
+}
+
+impl Display for FixedPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.int_cells, self.frac_cells)
+    }
+}
+
+/// How instruction addresses are displayed and entered
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AddressMode {
+    /// The index of the instruction in the program, in hex
+    Token,
+    /// The line and column in the source where the instruction starts
+    Source,
+}
+
+/// A write to a tape cell, as kept in the interpreter's change history
+#[derive(Debug, Copy, Clone)]
+pub struct CellChange {
+    pub address: usize,
+    /// The value the cell was set to
+    pub value: u32,
+    /// The address of the instruction that changed the cell
+    pub pc: usize,
+}
+
+/// The state a step overwrote, kept in the undo log so the step can be reversed. Steps only ever
+/// write to the cell under the pointer, and push or pop at most one loop.
+#[derive(Debug, Copy, Clone)]
+struct UndoEntry {
+    pc: usize,
+    ptr: usize,
+    /// The value of the cell under the pointer before the step
+    cell: u32,
+    loop_depth: usize,
+    /// The innermost loop before the step, in case the step exited it
+    innermost_loop: Option<usize>,
+    max_ptr: usize,
+    travel: u64,
+    output_bytes: u64,
+    last_output_step: u64,
+}
+
+/// A copy of an interpreter's execution state, taken with `Interpreter::checkpoint` so execution
+/// can return to it later with `Interpreter::restore`
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    tape: Vec<u32>,
+    pc: usize,
+    ptr: usize,
+    max_ptr: usize,
+    travel: u64,
+    steps: u64,
+    current_unit: usize,
+    loop_stack: Vec<usize>,
+    last_output_step: u64,
+}
+
+impl Checkpoint {
+    /// Returns the step count when the checkpoint was taken
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Returns the address of the instruction that was about to execute when the checkpoint was
+    /// taken
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+}
+
+/// A high-level characterization of the tape's contents
+#[derive(Debug, Clone)]
+pub struct TapeSummary {
+    pub nonzero_cells: usize,
+    /// The lowest and highest addresses of non-zero cells, if there are any
+    pub nonzero_range: Option<(usize, usize)>,
+    pub ptr: usize,
+    /// The most frequent non-zero values and how many cells hold them, most frequent first
+    pub top_values: Vec<(u32, usize)>,
+}
+
+/// Statistics over the region of the tape between the lowest and highest non-zero cells, hinting
+/// at whether a program built structured data or noise
+#[derive(Debug, Clone)]
+pub struct TapeStats {
+    /// How many cells the region spans
+    pub cells: usize,
+    pub distinct_values: usize,
+    /// The Shannon entropy of the region's values, in bits per cell (0 up to the cell width)
+    pub entropy: f64,
+    /// How many runs of equal values the region has, so run-length encoding would store it as
+    /// that many (value, length) pairs
+    pub runs: usize,
+}
+
+impl TapeStats {
+    /// The size of the region when run-length encoded as (value, length) byte pairs, relative to
+    /// its size as is. Below 1 means it compresses.
+    pub fn rle_ratio(&self) -> f64 {
+        (self.runs * 2) as f64 / self.cells as f64
+    }
+}
+
+/// An input that never has any data available, failing every read with
+/// `std::io::ErrorKind::WouldBlock`
+pub struct NoInput;
+
+impl Read for NoInput {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(ErrorKind::WouldBlock.into())
+    }
+}
+
+pub struct Interpreter<R: Read, W: Write> {
+    tape: Vec<u32>,
     program: Program,
     pc: usize,
     ptr: usize,
+    max_ptr: usize,
+    /// The total distance the pointer has moved, in cells
+    travel: u64,
+    steps: u64,
+    input_bytes: u64,
+    /// Every byte of input read so far, if input recording is enabled
+    consumed_input: Option<Vec<u8>>,
+    output_bytes: u64,
     input: R,
+    output: W,
+    /// Where output goes instead of `output` while running with `Interpreter::run_capture`
+    captured_output: Option<Vec<u8>>,
     eof_behaviour: EofBehaviour,
     current_unit: usize,
-    breakpoints: HashSet<usize>,
+    breakpoints: HashMap<usize, Breakpoint>,
+    data_breakpoints: Vec<DataBreakpoint>,
+    /// The index of the data breakpoint that stopped execution last, if it hasn't been taken yet
+    data_breakpoint_hit: Option<usize>,
+    watch_expressions: Vec<WatchExpression>,
+    /// The index of the watch expression that stopped execution last and its previous value, if it
+    /// hasn't been taken yet
+    watch_hit: Option<(usize, u64)>,
+    /// Ranges of cells that are read-only, where any write stops `Interpreter::cont`
+    protected: Vec<Range<usize>>,
+    /// The write to a protected cell that stopped execution last, if it hasn't been taken yet
+    protection_hit: Option<CellChange>,
+    loop_stack: Vec<usize>,
+    /// Steps executed under each chain of enclosing loops, if loop profiling is enabled
+    loop_profile: Option<HashMap<Vec<usize>, u64>>,
+    /// The state overwritten by every step so far, if undo logging is enabled
+    undo_log: Option<Vec<UndoEntry>>,
+    /// How many times each instruction was executed, if instruction profiling is enabled
+    pc_counts: Option<Vec<u64>>,
+    /// The most recent cell changes, oldest first, holding at most `change_history_size` entries
+    change_history: VecDeque<CellChange>,
+    change_history_size: usize,
+    /// Where every write to a tape cell is logged, if anywhere
+    write_log: Option<BufWriter<Box<dyn Write>>>,
+    /// Where the address of every instruction executed is logged, if anywhere
+    trace: Option<BufWriter<Box<dyn Write>>>,
+    delay: Duration,
+    /// Whether output is only flushed when needed, instead of after every byte
+    buffered_output: bool,
+    heat_colors: bool,
+    /// Whether `#` skips printing the state when it's identical to the last one it printed
+    collapse_state: bool,
+    /// The pc, pointer and tape hash of the last state printed by `#`
+    last_printed_state: Option<(usize, usize, u64)>,
+    /// How many `#` in a row were skipped since the last state printed
+    collapsed_states: u64,
+    signed_cells: bool,
+    /// How `Interpreter::fixed_point_at` reads regions of cells, if at all
+    fixed_point: Option<FixedPoint>,
+    /// The cell values shown as characters in tape dumps
+    printable: RangeInclusive<u8>,
+    ring_tape: bool,
+    cell_width: CellWidth,
+    /// The values arithmetic wraps within, if not the full range of a cell
+    cell_range: Option<RangeInclusive<u8>>,
+    max_output: Option<u64>,
+    /// The cell that must be non-zero for output to be written, if output is gated
+    output_gate: Option<usize>,
+    /// How many steps may run without any output before execution is aborted, if limited
+    output_watchdog: Option<u64>,
+    /// The step count right after the last output
+    last_output_step: u64,
+    wide_output: WideOutput,
+    output_endianness: Endianness,
+    address_mode: AddressMode,
+    /// Whether program dumps show the target of every bracket, as `-> 0x..`
+    jump_annotations: bool,
 }
 
-impl<R: Read> Interpreter<R> {
-    /// Create a new brainstorm interpreter
+impl<R: Read, W: Write> Interpreter<R, W> {
+    /// Create a new brainstorm interpreter, with every cell of the tape initialized to `fill_byte`
     pub fn new(
         program: Program,
         tape_size: usize,
+        fill_byte: u8,
         eof_behaviour: EofBehaviour,
         input: R,
-    ) -> Interpreter<R> {
+        output: W,
+    ) -> Interpreter<R, W> {
         Interpreter {
-            tape: vec![0; tape_size],
+            tape: vec![fill_byte as u32; tape_size],
             program,
             pc: 0,
             ptr: 0,
+            max_ptr: 0,
+            travel: 0,
+            steps: 0,
+            input_bytes: 0,
+            consumed_input: None,
+            output_bytes: 0,
             input,
+            output,
+            captured_output: None,
             eof_behaviour,
-            breakpoints: HashSet::new(),
+            breakpoints: HashMap::new(),
+            data_breakpoints: Vec::new(),
+            data_breakpoint_hit: None,
+            watch_expressions: Vec::new(),
+            watch_hit: None,
+            protected: Vec::new(),
+            protection_hit: None,
+            loop_stack: Vec::new(),
+            loop_profile: None,
+            undo_log: None,
+            pc_counts: None,
+            change_history: VecDeque::new(),
+            change_history_size: 0,
+            write_log: None,
+            trace: None,
             current_unit: 0,
+            delay: Duration::ZERO,
+            buffered_output: false,
+            heat_colors: false,
+            collapse_state: false,
+            last_printed_state: None,
+            collapsed_states: 0,
+            signed_cells: false,
+            fixed_point: None,
+            printable: 32..=126,
+            ring_tape: false,
+            cell_width: CellWidth::default(),
+            cell_range: None,
+            max_output: None,
+            output_gate: None,
+            output_watchdog: None,
+            last_output_step: 0,
+            wide_output: WideOutput::default(),
+            output_endianness: Endianness::default(),
+            address_mode: AddressMode::Token,
+            jump_annotations: true,
+        }
+    }
+
+    /// Sets a delay to sleep for between each step in `Interpreter::run`, to slow execution down to
+    /// a watchable pace
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Sets whether output may stay buffered in the output writer, instead of being flushed after
+    /// every byte. Buffered output is still flushed before reading input, before printing the
+    /// state, once the program halts and by `Interpreter::flush_output`, so this is only useful
+    /// with a buffered writer, where it avoids a write to the underlying stream per byte.
+    pub fn set_buffered_output(&mut self, buffered_output: bool) {
+        self.buffered_output = buffered_output;
+    }
+
+    /// Flushes any output still buffered in the output writer
+    pub fn flush_output(&mut self) -> Result<(), InterpreterError> {
+        self.output
+            .flush()
+            .map_err(|_| InterpreterError::OutputError)
+    }
+
+    /// Flushes the program's output and sleeps for the configured delay
+    fn pace(&mut self) -> Result<(), InterpreterError> {
+        self.output
+            .flush()
+            .map_err(|_| InterpreterError::OutputError)?;
+        std::thread::sleep(self.delay);
+        Ok(())
+    }
+
+    /// Sets how many of the most recent cell changes are remembered. Zero, the default, disables
+    /// the change history.
+    pub fn set_change_history_size(&mut self, size: usize) {
+        self.change_history_size = size;
+        while self.change_history.len() > size {
+            self.change_history.pop_front();
+        }
+    }
+
+    /// Returns the most recent cell changes, oldest first
+    pub fn change_history(&self) -> &VecDeque<CellChange> {
+        &self.change_history
+    }
+
+    /// Logs every write to a tape cell to `log`, as CSV lines of `step,pc,addr,old,new`
+    pub fn set_write_log(&mut self, log: Option<Box<dyn Write>>) -> Result<(), InterpreterError> {
+        self.write_log = log.map(BufWriter::new);
+        if let Some(log) = &mut self.write_log {
+            writeln!(log, "step,pc,addr,old,new").map_err(|_| InterpreterError::OutputError)?;
+        }
+        Ok(())
+    }
+
+    /// Logs the address of every instruction executed to `trace`, one per line in hex, so the run
+    /// can be replayed later
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write>>) {
+        self.trace = trace.map(BufWriter::new);
+    }
+
+    /// Flushes the write log and the trace, if there are any
+    pub fn flush_logs(&mut self) -> Result<(), InterpreterError> {
+        for log in [&mut self.write_log, &mut self.trace].into_iter().flatten() {
+            log.flush().map_err(|_| InterpreterError::OutputError)?;
+        }
+        Ok(())
+    }
+
+    /// Logs the address of the instruction about to be executed to the trace, if there is one
+    fn trace_instruction(&mut self) -> Result<(), InterpreterError> {
+        if let Some(trace) = &mut self.trace {
+            writeln!(trace, "{:#x}", self.pc).map_err(|_| InterpreterError::OutputError)?;
+        }
+        Ok(())
+    }
+
+    /// Records a change to the cell under the pointer, which held `old`, in the change history
+    /// and the write log, if they're enabled, and checks whether the cell was protected
+    fn record_change(&mut self, old: u32) -> Result<(), InterpreterError> {
+        if !self.protected.is_empty()
+            && self.protection_hit.is_none()
+            && self.protected.iter().any(|range| range.contains(&self.ptr))
+        {
+            self.protection_hit = Some(CellChange {
+                address: self.ptr,
+                value: self.tape[self.ptr],
+                pc: self.pc,
+            });
+        }
+
+        if let Some(log) = &mut self.write_log {
+            let width = self.cell_width.hex_digits() + 2;
+            writeln!(
+                log,
+                "{},{:#x},{:#x},{old:#0width$x},{:#0width$x}",
+                self.steps, self.pc, self.ptr, self.tape[self.ptr]
+            )
+            .map_err(|_| InterpreterError::OutputError)?;
+        }
+
+        if self.change_history_size == 0 {
+            return Ok(());
+        }
+        if self.change_history.len() == self.change_history_size {
+            self.change_history.pop_front();
+        }
+        self.change_history.push_back(CellChange {
+            address: self.ptr,
+            value: self.tape[self.ptr],
+            pc: self.pc,
+        });
+        Ok(())
+    }
+
+    /// Enables or disables counting how many steps run under each chain of enclosing loops. Any
+    /// counts collected so far are discarded.
+    pub fn set_loop_profiling(&mut self, enabled: bool) {
+        self.loop_profile = enabled.then(HashMap::new);
+    }
+
+    /// Returns the step counts collected by loop profiling, as pairs of the addresses of the
+    /// enclosing loops (outermost first) and the amount of steps run inside them, sorted by loops
+    pub fn loop_profile(&self) -> Vec<(&[usize], u64)> {
+        let mut profile: Vec<_> = self
+            .loop_profile
+            .iter()
+            .flatten()
+            .map(|(loops, &steps)| (loops.as_slice(), steps))
+            .collect();
+        profile.sort();
+        profile
+    }
+
+    /// Enables or disables recording every step so it can be undone with `Interpreter::step_back`.
+    /// Any steps recorded so far are discarded.
+    pub fn set_undo_log(&mut self, enabled: bool) {
+        self.undo_log = enabled.then(Vec::new);
+    }
+
+    /// Returns how many steps can be undone
+    pub fn undoable_steps(&self) -> usize {
+        self.undo_log.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Undoes the last step recorded in the undo log, restoring the program counter, pointer, loops
+    /// and the cell it changed, along with the counters that depend on them: the highest pointer,
+    /// the pointer travel, the output byte count, the output watchdog and the instruction and
+    /// loop profiles. Input that was read and output that was written can't be taken back, so the
+    /// input, the input byte count, the consumed input and the change history stay as they are.
+    /// Returns false if there's no step to undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.undo_log.as_mut().and_then(Vec::pop) else {
+            return false;
+        };
+        // The profile counted the step under the loops it left the interpreter in
+        if let Some(profile) = &mut self.loop_profile
+            && let Some(steps) = profile.get_mut(self.loop_stack.as_slice())
+        {
+            *steps -= 1;
+            if *steps == 0 {
+                profile.remove(self.loop_stack.as_slice());
+            }
+        }
+        if let Some(counts) = &mut self.pc_counts {
+            counts[entry.pc] = counts[entry.pc].saturating_sub(1);
+        }
+        self.pc = entry.pc;
+        self.ptr = entry.ptr;
+        self.tape[entry.ptr] = entry.cell;
+        self.loop_stack.truncate(entry.loop_depth);
+        if self.loop_stack.len() < entry.loop_depth
+            && let Some(innermost) = entry.innermost_loop
+        {
+            self.loop_stack.push(innermost);
         }
+        self.steps -= 1;
+        self.max_ptr = entry.max_ptr;
+        self.travel = entry.travel;
+        self.output_bytes = entry.output_bytes;
+        self.last_output_step = entry.last_output_step;
+        self.current_unit = self.program.unit_at(self.pc).unwrap_or(0);
+        true
+    }
+
+    /// Enables or disables keeping every byte of input the program reads. Any input kept so far is
+    /// discarded.
+    pub fn set_input_recording(&mut self, enabled: bool) {
+        self.consumed_input = enabled.then(Vec::new);
     }
 
-    fn hexdump_line(&self, start: usize, width: usize) {
+    /// Returns every byte of input read since input recording was enabled, if it is
+    pub fn consumed_input(&self) -> Option<&[u8]> {
+        self.consumed_input.as_deref()
+    }
+
+    /// Enables or disables counting how many times each instruction is executed. Any counts
+    /// collected so far are discarded.
+    pub fn set_pc_profiling(&mut self, enabled: bool) {
+        self.pc_counts = enabled.then(|| vec![0; self.program.tokens.len()]);
+    }
+
+    /// Returns how many times each instruction was executed, indexed by address, if instruction
+    /// profiling is enabled
+    pub fn pc_counts(&self) -> Option<&[u64]> {
+        self.pc_counts.as_deref()
+    }
+
+    /// Returns how many instructions of each kind were executed, named as in the `Token` enum and
+    /// in its order, if instruction profiling is enabled
+    pub fn opcode_stats(&self) -> Option<Vec<(&'static str, u64)>> {
+        let counts = self.pc_counts.as_ref()?;
+        let mut stats = vec![
+            ("Increment", 0),
+            ("Move", 0),
+            ("JumpZero", 0),
+            ("JumpNotZero", 0),
+            ("Input", 0),
+            ("Output", 0),
+            ("PrintState", 0),
+        ];
+        for (token, count) in self.program.tokens.iter().zip(counts) {
+            let kind = match token {
+                Token::Increment(_) => 0,
+                Token::Move(_) => 1,
+                Token::JumpZero(_) => 2,
+                Token::JumpNotZero(_) => 3,
+                Token::Input => 4,
+                Token::Output => 5,
+                Token::PrintState => 6,
+                Token::Eof => continue,
+            };
+            stats[kind].1 += count;
+        }
+        Some(stats)
+    }
+
+    /// Prints how many instructions of each kind were executed and their share of all steps, or a
+    /// note if instruction profiling isn't enabled
+    pub fn print_opcode_stats(&self) {
+        let Some(stats) = self.opcode_stats() else {
+            println!("Instruction profiling is not enabled");
+            return;
+        };
+        let total: u64 = stats.iter().map(|(_, count)| count).sum();
+        for (kind, count) in stats {
+            let share = if total == 0 {
+                0.0
+            } else {
+                count as f64 * 100.0 / total as f64
+            };
+            println!("{: <12} {count: >12} {share: >6.2}%", kind.yellow());
+        }
+        println!("{: <12} {total: >12}", "Total".yellow());
+    }
+
+    /// Sets whether the tape is a ring, where moving past either end wraps around to the other
+    /// instead of being an error. This changes the semantics of programs, so it's opt-in.
+    pub fn set_ring_tape(&mut self, ring_tape: bool) {
+        self.ring_tape = ring_tape;
+    }
+
+    /// Sets the maximum amount of bytes the program may output. Trying to output more stops the
+    /// program with `InterpreterError::OutputLimitExceeded`. There's no limit by default.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.max_output = max_output;
+    }
+
+    /// Makes output instructions do nothing while the cell at `gate` is zero, so a flag in the tape
+    /// can silence the program. Output is never gated by default.
+    pub fn set_output_gate(&mut self, gate: Option<usize>) {
+        self.output_gate = gate;
+    }
+
+    /// Sets how many steps the program may run without producing any output. Going past it stops
+    /// the program with `InterpreterError::OutputWatchdog`. There's no limit by default.
+    pub fn set_output_watchdog(&mut self, steps: Option<u64>) {
+        self.output_watchdog = steps;
+    }
+
+    /// Sets which cell values are shown as characters in tape dumps, with the rest shown as a
+    /// placeholder. Defaults to printable ASCII, from 0x20 to 0x7e.
+    pub fn set_printable_range(&mut self, printable: RangeInclusive<u8>) {
+        self.printable = printable;
+    }
+
+    /// Returns the character a cell holding `value` is shown as in tape dumps, which is a
+    /// placeholder unless it's in the printable range
+    pub fn display_char(&self, value: u32) -> char {
+        display_char(value, &self.printable)
+    }
+
+    /// Sets how many bits every cell holds, which is 8 by default. `+` and `-` wrap around at the
+    /// cell's width, while `,` still stores a byte and `.` still writes the low byte unless wide
+    /// output is set. Cells wider than the new width are truncated.
+    pub fn set_cell_width(&mut self, cell_width: CellWidth) {
+        self.cell_width = cell_width;
+        for cell in &mut self.tape {
+            *cell &= cell_width.max();
+        }
+    }
+
+    /// Returns how many bits every cell holds
+    pub fn cell_width(&self) -> CellWidth {
+        self.cell_width
+    }
+
+    /// Makes `+` and `-` wrap within an inclusive range of values instead of the full range of a
+    /// cell, to simulate cells such as decimal digits. With the range `lo..=hi`, adding `n` to a
+    /// cell holding `v` leaves `lo + (v - lo + n) mod (hi - lo + 1)`, using the mathematical
+    /// modulo, so incrementing `hi` gives `lo` and decrementing `lo` gives `hi`. A cell outside of
+    /// the range (from the fill byte, input or a preset) is brought into it by its first change.
+    /// A folded run of `+` and `-` wraps the same as its commands would one at a time.
+    pub fn set_cell_range(&mut self, range: Option<RangeInclusive<u8>>) {
+        self.cell_range = range;
+    }
+
+    /// Returns the value a cell holding `cell` is left with after adding `value`, wrapping around
+    /// the cell range or width
+    fn add_to_cell(&self, cell: u32, value: i32) -> u32 {
+        match &self.cell_range {
+            None => cell.wrapping_add(value as u32) & self.cell_width.max(),
+            Some(range) => {
+                let low = *range.start() as i64;
+                let size = *range.end() as i64 - low + 1;
+                let step = (value as i64).rem_euclid(size);
+                (low + (cell as i64 - low + step).rem_euclid(size)) as u32
+            }
+        }
+    }
+
+    /// Sets how `.` turns a cell into output bytes, and the byte order used by
+    /// `WideOutput::AllBytes`
+    pub fn set_wide_output(&mut self, wide_output: WideOutput, endianness: Endianness) {
+        self.wide_output = wide_output;
+        self.output_endianness = endianness;
+    }
+
+    /// Sets what happens to the cell under the pointer when input is read past its end
+    pub fn set_eof_behaviour(&mut self, eof_behaviour: EofBehaviour) {
+        self.eof_behaviour = eof_behaviour;
+    }
+
+    /// Sets whether the hexdump colors cells on a gradient by their value
+    pub fn set_heat_colors(&mut self, heat_colors: bool) {
+        self.heat_colors = heat_colors;
+    }
+
+    /// Sets whether `#` skips printing the state when it's identical (same pc, pointer and tape) to
+    /// the last state it printed, counting the skipped prints instead
+    pub fn set_collapse_state(&mut self, collapse_state: bool) {
+        self.collapse_state = collapse_state;
+    }
+
+    /// Sets whether cell values are displayed as signed (-128 to 127) rather than unsigned. This
+    /// only affects displays, as arithmetic wraps either way.
+    pub fn set_signed_cells(&mut self, signed_cells: bool) {
+        self.signed_cells = signed_cells;
+    }
+
+    /// Sets how regions of cells are read as fixed-point numbers in displays, if at all. This only
+    /// affects displays.
+    pub fn set_fixed_point(&mut self, fixed_point: Option<FixedPoint>) {
+        self.fixed_point = fixed_point;
+    }
+
+    /// Reads the region of cells starting at `address` as a fixed-point number, formatted as a
+    /// decimal, if a fixed-point layout is set, the region fits in the tape and its cells span at
+    /// most `FixedPoint::MAX_BITS`
+    pub fn fixed_point_at(&self, address: usize) -> Option<(FixedPoint, String)> {
+        let layout = self.fixed_point?;
+        let bits = self.cell_width.bits();
+        if layout.cells() as u32 * bits > FixedPoint::MAX_BITS {
+            return None;
+        }
+        let cells = self
+            .tape
+            .get(address..address.checked_add(layout.cells())?)?;
+        Some((layout, layout.format(cells, bits)))
+    }
+
+    /// Formats a cell value for display, honoring the signed cells setting and the cell width
+    fn format_cell(&self, value: u32) -> String {
+        if self.signed_cells {
+            self.cell_width.signed(value).to_string()
+        } else {
+            format!(
+                "{value:#0width$x}",
+                width = self.cell_width.hex_digits() + 2
+            )
+        }
+    }
+
+    /// Returns the style a tape cell is printed with in the hexdump
+    fn cell_style(&self, address: usize) -> Style {
+        if !self.heat_colors {
+            return if address == self.ptr {
+                Style::new().green()
+            } else {
+                Style::new()
+            };
+        }
+
+        if address == self.ptr {
+            return Style::new().black().on_green();
+        }
+        match self.tape[address] {
+            0 => Style::new().dimmed(),
+            value => {
+                // Gradient from a dim blue for low values to a bright yellow for high values
+                let max = self.cell_width.max() as i64;
+                let lerp = |from: i64, to: i64| (from + (to - from) * value as i64 / max) as u8;
+                Style::new().truecolor(lerp(60, 255), lerp(60, 230), lerp(150, 40))
+            }
+        }
+    }
+
+    fn hexdump_line(&self, start: usize, width: usize, labels: &[(usize, &str)]) {
         print!(" {:#0width$x}  ", start.yellow());
+        // Wide enough for any value, including a sign
+        let digits = if self.signed_cells {
+            self.cell_width
+                .signed(1 << (self.cell_width.bits() - 1))
+                .to_string()
+                .len()
+        } else {
+            self.cell_width.hex_digits()
+        };
         for i in 0..16 {
             if i == 8 {
                 print!(" ");
             }
-            if start + i < self.tape.len() {
-                if start + i == self.ptr {
-                    print!("{:02X} ", self.tape[start + i].green());
-                } else {
-                    print!("{:02X} ", self.tape[start + i]);
-                };
+            if start + i >= self.tape.len() {
+                print!("{: <1$}", "", digits + 1);
+            } else if self.signed_cells {
+                print!(
+                    "{:>digits$} ",
+                    self.cell_width
+                        .signed(self.tape[start + i])
+                        .style(self.cell_style(start + i))
+                );
             } else {
-                print!("   ");
+                print!(
+                    "{:0digits$X} ",
+                    self.tape[start + i].style(self.cell_style(start + i))
+                );
             }
         }
 
         print!("   ");
 
         for i in 0..16 {
-            if start + i >= self.tape.len() {
-                break;
-            }
             if i == 8 {
                 print!(" ");
             }
-            let char = self.tape[start + i];
-            let char = if (32..=176).contains(&char) {
-                char as char
-            } else {
-                '·'
-            };
-            if start + i == self.ptr {
-                print!("{} ", char.green());
-            } else {
-                print!("{char} ");
+            if start + i >= self.tape.len() {
+                // Padded so the labels of a short last line line up with the others
+                print!("  ");
+                continue;
+            }
+            let char = display_char(self.tape[start + i], &self.printable);
+            print!("{} ", char.style(self.cell_style(start + i)));
+        }
+
+        for (address, label) in labels {
+            if (start..start + 16).contains(address) {
+                print!(" {}", format!("{label}@{address:#x}").cyan());
             }
         }
 
         println!();
     }
 
+    /// Sets whether instruction addresses are shown as token indices or as source positions
+    pub fn set_address_mode(&mut self, address_mode: AddressMode) {
+        self.address_mode = address_mode;
+    }
+
+    /// Returns how instruction addresses are shown
+    pub fn address_mode(&self) -> AddressMode {
+        self.address_mode
+    }
+
+    /// Sets whether program dumps show the target of every bracket after it. On by default.
+    pub fn set_jump_annotations(&mut self, jump_annotations: bool) {
+        self.jump_annotations = jump_annotations;
+    }
+
+    /// Returns whether program dumps show the target of every bracket
+    pub fn jump_annotations(&self) -> bool {
+        self.jump_annotations
+    }
+
+    /// Formats an instruction address for display according to the address mode. Source
+    /// positions are shown as `line:column`, falling back to the token index if unknown.
+    pub fn format_address(&self, address: usize) -> String {
+        match (self.address_mode, self.program.positions.get(address)) {
+            (AddressMode::Source, Some(position)) => match position.column {
+                Some(column) => format!("{}:{column}", position.line),
+                None => position.line.to_string(),
+            },
+            _ => format!("{address:#x}"),
+        }
+    }
+
+    /// Formats an instruction address padded to `width`, with hex addresses zero-padded
+    fn padded_address(&self, address: usize, width: usize) -> String {
+        match self.address_mode {
+            AddressMode::Token => format!("{address:#0width$x}"),
+            AddressMode::Source => format!("{: <width$}", self.format_address(address)),
+        }
+    }
+
+    /// Finds the instruction at a source position, or the first one after it. The column may be
+    /// omitted to find the first instruction on a line.
+    pub fn address_at_source(&self, line: usize, column: Option<usize>) -> Option<usize> {
+        self.program.positions.iter().position(|position| {
+            position.line > line
+                || (position.line == line
+                    && column.is_none_or(|column| {
+                        position
+                            .column
+                            .is_none_or(|position_column| position_column >= column)
+                    }))
+        })
+    }
+
     fn dump_program_range(
         &self,
         unit_name: &str,
@@ -119,7 +1090,10 @@ impl<R: Read> Interpreter<R> {
         // This entire function is beyond ugly, as I just kept expanding it to add more features rather than refactoring
         // I might fix it at some point.
         let mut output = String::new();
-        let width = format!("{:#x}", self.program.tokens.len() - 1).len();
+        let width = (0..self.program.tokens.len())
+            .map(|address| self.format_address(address).len())
+            .max()
+            .unwrap_or(0);
         let mut green_line = None;
 
         const EMPTY: &str = "";
@@ -128,8 +1102,8 @@ impl<R: Read> Interpreter<R> {
         // so .unwrap() is safe to use here.
         write!(
             output,
-            "{:#0width$x}  {}",
-            start.yellow(),
+            "{}  {}",
+            self.padded_address(start, width).yellow(),
             unit_name.yellow()
         )
         .unwrap();
@@ -153,8 +1127,8 @@ impl<R: Read> Interpreter<R> {
             if next_on_new_line || tokens_since_new_line >= 5 {
                 write!(
                     output,
-                    "\n{:#0width$x}    {EMPTY: <indentation$}",
-                    i.dimmed()
+                    "\n{}    {EMPTY: <indentation$}",
+                    self.padded_address(i, width).dimmed()
                 )
                 .unwrap();
                 next_on_new_line = false;
@@ -162,8 +1136,10 @@ impl<R: Read> Interpreter<R> {
             }
 
             let mut style = Style::new();
-            if self.breakpoints.contains(&i) {
-                style = style.underline().red();
+            match self.breakpoints.get(&i) {
+                Some(breakpoint) if breakpoint.enabled => style = style.underline().red(),
+                Some(_) => style = style.underline(),
+                None => (),
             }
             if i == self.pc {
                 style = style.green();
@@ -174,12 +1150,28 @@ impl<R: Read> Interpreter<R> {
             match token {
                 Token::JumpNotZero(t) => {
                     next_on_new_line = true;
-                    write!(output, " {} {:#x}", "->".dimmed(), (t - 1).dimmed()).unwrap();
+                    if self.jump_annotations {
+                        write!(
+                            output,
+                            " {} {}",
+                            "->".dimmed(),
+                            self.format_address(t - 1).dimmed()
+                        )
+                        .unwrap();
+                    }
                 }
                 Token::JumpZero(t) => {
                     *indentation += 2;
                     next_on_new_line = true;
-                    write!(output, " {} {:#x}", "->".dimmed(), (t - 1).dimmed()).unwrap();
+                    if self.jump_annotations {
+                        write!(
+                            output,
+                            " {} {}",
+                            "->".dimmed(),
+                            self.format_address(t - 1).dimmed()
+                        )
+                        .unwrap();
+                    }
                 }
                 _ => (),
             }
@@ -212,43 +1204,478 @@ impl<R: Read> Interpreter<R> {
         (result, green_line)
     }
 
-    /// Dumps a certain amount of lines before and after the current executing instruction
-    pub fn dump_current_program_section(&self, before: usize, after: usize) {
-        let dump = self.dump_program();
-        println!("Printing {} to {} around {}", before, after, dump.1);
-        for line in dump
-            .0
-            .lines()
-            .skip(dump.1.saturating_sub(before))
-            .take(before + 1 + after)
-        {
-            println!("{line}");
+    /// Renders the current instruction on a single line, with its address and unit
+    pub fn current_instruction(&self) -> String {
+        format!(
+            "{}    {}    {}",
+            self.format_address(self.pc).dimmed(),
+            self.program.tokens[self.pc].green(),
+            self.program.units[self.current_unit].description.dimmed()
+        )
+    }
+
+    /// Renders the program as an indented tree with one instruction per line, where the body of
+    /// each loop is nested between its brackets. Only instructions within `range` are included,
+    /// although they keep the indentation of their place in the whole program. The current
+    /// instruction is highlighted.
+    pub fn program_tree(&self, range: Range<usize>) -> String {
+        let width = (0..self.program.tokens.len())
+            .map(|address| self.format_address(address).len())
+            .max()
+            .unwrap_or(0);
+        let mut output = String::new();
+        self.write_tree(
+            &self.program.to_ast(),
+            &mut 0,
+            0,
+            &range,
+            width,
+            &mut output,
+        );
+        output
+    }
+
+    /// Writes the lines of `Interpreter::program_tree` for a list of nodes, the first of which is
+    /// at `address`. `address` is left past the last node.
+    fn write_tree(
+        &self,
+        nodes: &[Node],
+        address: &mut usize,
+        depth: usize,
+        range: &Range<usize>,
+        width: usize,
+        output: &mut String,
+    ) {
+        const INDENT: &str = "    ";
+
+        let line = |output: &mut String, address: usize, text: &str| {
+            if range.contains(&address) {
+                let style = if address == self.pc {
+                    Style::new().green()
+                } else {
+                    Style::new()
+                };
+                // Writing to a String cannot fail, so .unwrap() is safe to use here.
+                writeln!(
+                    output,
+                    "{}    {}{}",
+                    self.padded_address(address, width).dimmed(),
+                    INDENT.repeat(depth),
+                    text.style(style)
+                )
+                .unwrap();
+            }
+        };
+        for node in nodes {
+            match node {
+                Node::Op(token) => {
+                    line(output, *address, &token.to_string());
+                    *address += 1;
+                }
+                Node::Loop(body) => {
+                    line(output, *address, "[");
+                    *address += 1;
+                    self.write_tree(body, address, depth + 1, range, width, output);
+                    line(output, *address, "]");
+                    *address += 1;
+                }
+            }
+        }
+    }
+
+    /// Dumps a certain amount of lines before and after the current executing instruction
+    pub fn dump_current_program_section(&self, before: usize, after: usize) {
+        let dump = self.dump_program();
+        println!("Printing {} to {} around {}", before, after, dump.1);
+        for line in dump
+            .0
+            .lines()
+            .skip(dump.1.saturating_sub(before))
+            .take(before + 1 + after)
+        {
+            println!("{line}");
+        }
+    }
+
+    /// Prints a hexdump of the tape, skipping over lines that are at zero
+    pub fn print_tape(&self) {
+        self.print_tape_labeled(&[]);
+    }
+
+    /// Prints a hexdump of the tape like `Interpreter::print_tape`, naming the labeled addresses at
+    /// the end of their lines. Lines with a label are never skipped. Returns how many lines were
+    /// printed.
+    pub fn print_tape_labeled(&self, labels: &[(usize, &str)]) -> usize {
+        let address_width = format!("{:#x}", self.tape.len()).len();
+
+        let mut first_all_zeroes = false;
+        let mut ellipsis = false;
+        let mut lines = 0;
+
+        for i in (0..self.tape.len()).step_by(16) {
+            let labeled = labels
+                .iter()
+                .any(|(address, _)| (i..i + 16).contains(address));
+            // The last line is short when the tape's size isn't a multiple of 16
+            let line = &self.tape[i..(i + 16).min(self.tape.len())];
+            if !labeled && line.iter().all(|&c| c == 0) {
+                if !first_all_zeroes {
+                    self.hexdump_line(i, address_width, labels);
+                    first_all_zeroes = true;
+                    lines += 1;
+                } else if !ellipsis {
+                    println!("{: <width$}   ....", "", width = address_width);
+                    ellipsis = true;
+                    lines += 1;
+                }
+                continue;
+            } else {
+                first_all_zeroes = false;
+                ellipsis = false;
+            }
+            self.hexdump_line(i, address_width, labels);
+            lines += 1;
+        }
+        lines
+    }
+
+    /// Prints the cells from `depth` below to `depth` above the pointer as a vertical stack, with
+    /// the highest address on top and the pointer's cell marked
+    pub fn print_stack(&self, depth: usize) {
+        let (start, cells) = self.tape_window(self.ptr, depth);
+        let address_width = format!("{:#x}", self.tape.len()).len();
+        if start + cells.len() < self.ptr + depth + 1 {
+            println!("{: <address_width$}     (end of tape)", "");
+        }
+        for (offset, &value) in cells.iter().enumerate().rev() {
+            let address = start + offset;
+            let marker = if address == self.ptr { "->" } else { "  " };
+            println!(
+                "{} {:#0address_width$x}  {:>4}  {}",
+                marker.green(),
+                address.yellow(),
+                self.format_cell(value).style(self.cell_style(address)),
+                display_char(value, &self.printable).style(self.cell_style(address))
+            );
+        }
+        if start + depth > self.ptr {
+            println!("{: <address_width$}     (start of tape)", "");
+        }
+    }
+
+    /// Summarizes the tape, listing up to `top` of the most frequent non-zero values
+    pub fn tape_summary(&self, top: usize) -> TapeSummary {
+        let counts = value_counts(&self.tape);
+        let mut top_values: Vec<_> = counts
+            .iter()
+            .filter(|&(&value, _)| value != 0)
+            .map(|(&value, &count)| (value, count))
+            .collect();
+        top_values.sort_by_key(|&(value, count)| (std::cmp::Reverse(count), value));
+        top_values.truncate(top);
+
+        TapeSummary {
+            nonzero_cells: self.tape.len() - counts.get(&0).copied().unwrap_or(0),
+            nonzero_range: self
+                .tape
+                .iter()
+                .position(|&cell| cell != 0)
+                .zip(self.tape.iter().rposition(|&cell| cell != 0)),
+            ptr: self.ptr,
+            top_values,
+        }
+    }
+
+    /// Computes statistics over the region between the lowest and highest non-zero cells, or
+    /// returns None if the whole tape is zero
+    pub fn tape_stats(&self) -> Option<TapeStats> {
+        let lowest = self.tape.iter().position(|&cell| cell != 0)?;
+        let highest = self.tape.iter().rposition(|&cell| cell != 0)?;
+        let region = &self.tape[lowest..=highest];
+
+        let counts = value_counts(region);
+        let entropy = counts
+            .values()
+            .map(|&count| {
+                let p = count as f64 / region.len() as f64;
+                -p * p.log2()
+            })
+            .sum::<f64>();
+
+        Some(TapeStats {
+            cells: region.len(),
+            distinct_values: counts.len(),
+            entropy: entropy.max(0.0),
+            runs: 1 + region.windows(2).filter(|pair| pair[0] != pair[1]).count(),
+        })
+    }
+
+    /// Prints the statistics from `Interpreter::tape_stats`
+    pub fn print_tape_stats(&self) {
+        let Some(stats) = self.tape_stats() else {
+            println!("{: <16} -", "Region stats".yellow());
+            return;
+        };
+        println!("{: <16} {} cells", "Region".yellow(), stats.cells);
+        println!(
+            "{: <16} {}",
+            "Distinct values".yellow(),
+            stats.distinct_values
+        );
+        println!(
+            "{: <16} {:.3} bits per cell",
+            "Entropy".yellow(),
+            stats.entropy
+        );
+        println!(
+            "{: <16} {} runs, {:.2} of the region's size",
+            "RLE".yellow(),
+            stats.runs,
+            stats.rle_ratio()
+        );
+    }
+
+    /// Prints a table summarizing the tape, as a quick glance at what the program left in memory
+    pub fn print_tape_summary(&self) {
+        let summary = self.tape_summary(5);
+        println!(
+            "{: <16} {}",
+            "Non-zero cells".yellow(),
+            summary.nonzero_cells
+        );
+        match summary.nonzero_range {
+            Some((lowest, highest)) => {
+                println!("{: <16} {lowest:#x}", "Lowest non-zero".yellow());
+                println!("{: <16} {highest:#x}", "Highest non-zero".yellow());
+            }
+            None => println!("{: <16} -", "Non-zero range".yellow()),
+        }
+        println!("{: <16} {:#x}", "Pointer".yellow(), summary.ptr);
+        print!("{: <16}", "Top values".yellow());
+        for (value, count) in &summary.top_values {
+            print!(" {} x{count}", self.format_cell(*value));
+        }
+        println!();
+        self.print_tape_stats();
+    }
+
+    /// Prints the interpreter's registers and a short summary of its execution
+    pub fn print_registers(&self) {
+        println!("{}: {}", "PC".yellow(), self.format_address(self.pc));
+        println!("{}: {:#0x}", "TP".yellow(), self.ptr);
+        println!("{}: {:#0x}", "Max TP".yellow(), self.max_ptr);
+        println!("{}: {}", "Steps".yellow(), self.steps);
+        println!("{}: {}", "Travel".yellow(), self.travel);
+        println!(
+            "{}: {}",
+            "Current Unit".yellow(),
+            self.program.units[self.current_unit].description
+        );
+    }
+
+    /// Returns the tape
+    pub fn tape(&self) -> &[u32] {
+        &self.tape
+    }
+
+    /// Returns the cells within `radius` of `center`, clamped to the edges of the tape, along with
+    /// the address of the first returned cell
+    pub fn tape_window(&self, center: usize, radius: usize) -> (usize, &[u32]) {
+        let start = center.saturating_sub(radius).min(self.tape.len());
+        let end = center
+            .saturating_add(radius)
+            .saturating_add(1)
+            .min(self.tape.len());
+        (start, &self.tape[start..end])
+    }
+
+    /// Returns a 64-bit FNV-1a hash of the tape. Trailing zero cells are left out, so the hash of a
+    /// given memory state doesn't depend on the tape size.
+    pub fn tape_hash(&self) -> u64 {
+        let end = self
+            .tape
+            .iter()
+            .rposition(|&cell| cell != 0)
+            .map_or(0, |last| last + 1);
+        self.tape[..end]
+            .iter()
+            .fold(0xcbf29ce484222325, |hash, &cell| {
+                (hash ^ cell as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Overwrites the cells starting at `address` with the given values, truncated to the cell
+    /// width, without recording the changes or checking protections. Panics if the values don't
+    /// fit in the tape.
+    pub fn write_cells(&mut self, address: usize, values: &[u32]) {
+        for (cell, value) in self.tape[address..address + values.len()]
+            .iter_mut()
+            .zip(values)
+        {
+            *cell = value & self.cell_width.max();
+        }
+    }
+
+    /// Appends another program's code to the end of the running program, so execution continues
+    /// into it once the current code is done. If the program had halted, it resumes with the new
+    /// code.
+    pub fn append_program(&mut self, other: &Program) {
+        self.program.append(other);
+        if let Some(counts) = &mut self.pc_counts {
+            counts.resize(self.program.tokens.len(), 0);
+        }
+    }
+
+    /// Creates an interpreter that runs another program on a copy of this interpreter's tape and
+    /// pointer, with no input and its output captured, so the live session isn't affected
+    pub fn sandbox(&self, program: Program) -> Interpreter<std::io::Empty, Vec<u8>> {
+        let mut sandbox = Interpreter::new(
+            program,
+            self.tape.len(),
+            0,
+            self.eof_behaviour,
+            std::io::empty(),
+            Vec::new(),
+        );
+        sandbox.tape.copy_from_slice(&self.tape);
+        sandbox.ptr = self.ptr;
+        sandbox.max_ptr = self.ptr;
+        sandbox.ring_tape = self.ring_tape;
+        sandbox.cell_width = self.cell_width;
+        sandbox.cell_range = self.cell_range.clone();
+        sandbox.signed_cells = self.signed_cells;
+        sandbox.printable = self.printable.clone();
+        // The sandbox counts its steps from 0, so its watchdog starts counting from there too
+        sandbox.output_watchdog = self.output_watchdog;
+        sandbox
+    }
+
+    /// Creates a copy of this interpreter's whole execution state, breakpoints included, so it can
+    /// be run ahead without affecting the live session. The copy's output is discarded, and it has
+    /// no input: reading fails with `InterpreterError::InputWouldBlock`, since the live input can't
+    /// be read without consuming it.
+    pub fn fork(&self) -> Interpreter<NoInput, std::io::Sink> {
+        let mut fork = Interpreter::new(
+            self.program.clone(),
+            0,
+            0,
+            self.eof_behaviour,
+            NoInput,
+            std::io::sink(),
+        );
+        fork.tape = self.tape.clone();
+        fork.pc = self.pc;
+        fork.ptr = self.ptr;
+        fork.max_ptr = self.max_ptr;
+        fork.travel = self.travel;
+        fork.steps = self.steps;
+        fork.input_bytes = self.input_bytes;
+        fork.output_bytes = self.output_bytes;
+        fork.current_unit = self.current_unit;
+        fork.breakpoints = self.breakpoints.clone();
+        fork.data_breakpoints = self.data_breakpoints.clone();
+        fork.watch_expressions = self.watch_expressions.clone();
+        fork.protected = self.protected.clone();
+        fork.loop_stack = self.loop_stack.clone();
+        fork.signed_cells = self.signed_cells;
+        fork.printable = self.printable.clone();
+        fork.ring_tape = self.ring_tape;
+        fork.cell_width = self.cell_width;
+        fork.cell_range = self.cell_range.clone();
+        fork.max_output = self.max_output;
+        fork.output_watchdog = self.output_watchdog;
+        fork.last_output_step = self.last_output_step;
+        fork.output_gate = self.output_gate;
+        fork.wide_output = self.wide_output;
+        fork.output_endianness = self.output_endianness;
+        fork.address_mode = self.address_mode;
+        fork.jump_annotations = self.jump_annotations;
+        fork
+    }
+
+    /// Saves the current execution state: the tape, registers and loops, along with the counters
+    /// that depend on them. Breakpoints and options aren't part of it.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            tape: self.tape.clone(),
+            pc: self.pc,
+            ptr: self.ptr,
+            max_ptr: self.max_ptr,
+            travel: self.travel,
+            steps: self.steps,
+            current_unit: self.current_unit,
+            loop_stack: self.loop_stack.clone(),
+            last_output_step: self.last_output_step,
+        }
+    }
+
+    /// Returns to a state saved with `Interpreter::checkpoint`. Input that was read and output that
+    /// was written since can't be taken back, so they stay as they are. The undo log and change
+    /// history are cleared, since they describe steps that no longer lead to the current state.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.tape.clone_from(&checkpoint.tape);
+        self.pc = checkpoint.pc;
+        self.ptr = checkpoint.ptr;
+        self.max_ptr = checkpoint.max_ptr;
+        self.travel = checkpoint.travel;
+        self.steps = checkpoint.steps;
+        self.current_unit = checkpoint.current_unit;
+        self.loop_stack.clone_from(&checkpoint.loop_stack);
+        self.last_output_step = checkpoint.last_output_step;
+        if let Some(log) = &mut self.undo_log {
+            log.clear();
         }
+        self.change_history.clear();
     }
 
-    /// Prints a hexdump of the tape, skipping over lines that are at zero
-    pub fn print_tape(&self) {
-        let address_width = format!("{:#x}", self.tape.len()).len();
+    /// Returns the writer the program's output goes to
+    pub fn output(&self) -> &W {
+        &self.output
+    }
 
-        let mut first_all_zeroes = false;
-        let mut ellipsis = false;
+    /// Returns the address of the current instruction
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
 
-        for i in (0..self.tape.len()).step_by(16) {
-            if self.tape[i..i + 16].iter().all(|&c| c == 0) {
-                if !first_all_zeroes {
-                    self.hexdump_line(i, address_width);
-                    first_all_zeroes = true;
-                } else if !ellipsis {
-                    println!("{: <width$}   ....", "", width = address_width);
-                    ellipsis = true;
-                }
-                continue;
-            } else {
-                first_all_zeroes = false;
-                ellipsis = false;
-            }
-            self.hexdump_line(i, address_width);
-        }
+    /// Returns the address of the cell under the pointer
+    pub fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    /// Returns the program being executed
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Returns the index of the unit the current instruction belongs to
+    pub fn current_unit(&self) -> usize {
+        self.current_unit
+    }
+
+    /// Returns the amount of instructions executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Returns the amount of bytes of input read by the program so far
+    pub fn input_bytes(&self) -> u64 {
+        self.input_bytes
+    }
+
+    /// Returns the amount of bytes the program has output so far
+    pub fn output_bytes(&self) -> u64 {
+        self.output_bytes
+    }
+
+    /// Returns the highest tape address the pointer has reached so far
+    pub fn max_ptr(&self) -> usize {
+        self.max_ptr
+    }
+
+    /// Returns the total distance the pointer has moved so far, in cells
+    pub fn travel(&self) -> u64 {
+        self.travel
     }
 
     /// Prints the internal state of the interpreter
@@ -266,23 +1693,137 @@ impl<R: Read> Interpreter<R> {
 
         println!();
         println!("{}", "Registers:".blue().bold());
-        println!("{}: {:#0x}", "PC".yellow(), self.pc);
-        println!("{}: {:#0x}", "TP".yellow(), self.ptr);
-        println!(
-            "{}: {}",
-            "Current Unit".yellow(),
-            self.program.units[self.current_unit].description
-        );
+        self.print_registers();
 
         println!(
             "{}", "=========================================== END CTX ===========================================".red()
         );
     }
 
+    /// Prints the internal state of the interpreter for a `#` command, unless states are being
+    /// collapsed and it's unchanged since the last one
+    fn print_state_command(&mut self) {
+        if self.collapse_state {
+            let state = (self.pc, self.ptr, self.tape_hash());
+            if self.last_printed_state == Some(state) {
+                self.collapsed_states += 1;
+                return;
+            }
+            self.flush_collapsed_states();
+            self.last_printed_state = Some(state);
+        }
+        self.print_state();
+    }
+
+    /// Prints how many times in a row `#` skipped an unchanged state, if it did
+    fn flush_collapsed_states(&mut self) {
+        if self.collapsed_states > 0 {
+            println!("(state unchanged, {}x)", self.collapsed_states);
+            self.collapsed_states = 0;
+        }
+    }
+
+    /// Describes, in plain English, what the instruction at the current PC is about to do
+    pub fn explain(&self) -> String {
+        let Some(token) = self.program.tokens.get(self.pc) else {
+            return "the program counter is outside of the program".to_string();
+        };
+        let cell = self.tape[self.ptr];
+
+        match *token {
+            Token::Increment(value) => {
+                let after = self.add_to_cell(cell, value);
+                let action = if value > 0 {
+                    format!("add {value} to")
+                } else {
+                    format!("subtract {} from", value.unsigned_abs())
+                };
+                format!(
+                    "{action} the current cell (cell {:#x}, value {} -> {})",
+                    self.ptr,
+                    self.format_cell(cell),
+                    self.format_cell(after)
+                )
+            }
+            Token::Move(value) => {
+                let direction = if value > 0 { "right" } else { "left" };
+                let target = self.ptr.wrapping_add(value as usize);
+                if target >= self.tape.len() && self.ring_tape {
+                    let offset = value.rem_euclid(self.tape.len() as isize) as usize;
+                    format!(
+                        "move the pointer {direction} by {} cells ({:#x} -> {:#x}, wrapping around the ring tape)",
+                        value.unsigned_abs(),
+                        self.ptr,
+                        (self.ptr + offset) % self.tape.len()
+                    )
+                } else if target >= self.tape.len() {
+                    format!(
+                        "move the pointer {direction} by {} cells (from {:#x}, which would leave the tape)",
+                        value.unsigned_abs(),
+                        self.ptr
+                    )
+                } else {
+                    format!(
+                        "move the pointer {direction} by {} cells ({:#x} -> {target:#x})",
+                        value.unsigned_abs(),
+                        self.ptr
+                    )
+                }
+            }
+            Token::JumpZero(target) => format!(
+                "if the current cell is zero, jump past the matching ] at {:#x} (cell {:#x} is {}, so the jump is {})",
+                target - 1,
+                self.ptr,
+                self.format_cell(cell),
+                if cell == 0 { "taken" } else { "not taken" }
+            ),
+            Token::JumpNotZero(target) => format!(
+                "if the current cell is not zero, jump back to the matching [ at {:#x} (cell {:#x} is {}, so the jump is {})",
+                target - 1,
+                self.ptr,
+                self.format_cell(cell),
+                if cell != 0 { "taken" } else { "not taken" }
+            ),
+            Token::Input => format!(
+                "read one byte of input into the current cell (cell {:#x}); on end of input: {}",
+                self.ptr, self.eof_behaviour
+            ),
+            Token::Output => {
+                let char = display_char(cell, &self.printable);
+                format!(
+                    "write the current cell to the output (cell {:#x}, value {} '{char}')",
+                    self.ptr,
+                    self.format_cell(cell)
+                )
+            }
+            Token::PrintState => "print the interpreter's internal state".to_string(),
+            Token::Eof => "end of the program, execution halts here".to_string(),
+        }
+    }
+
     /// Takes a single step in the interpreter. Returns OK(true) if there's still more program to
     /// execute, and Ok(false) if the program has halted (reached EOF). May return an error if the
     /// brainfuck program tries to move outside the tape, or if IO fails
     pub fn step(&mut self) -> Result<bool, InterpreterError> {
+        // Input is only traced once it's available, since an input that would block isn't executed
+        if !matches!(
+            self.program.tokens.get(self.pc),
+            Some(Token::Eof | Token::Input)
+        ) {
+            self.trace_instruction()?;
+        }
+        let executed = self.pc;
+        let undo = self.undo_log.is_some().then(|| UndoEntry {
+            pc: self.pc,
+            ptr: self.ptr,
+            cell: self.tape[self.ptr],
+            loop_depth: self.loop_stack.len(),
+            innermost_loop: self.loop_stack.last().copied(),
+            max_ptr: self.max_ptr,
+            travel: self.travel,
+            output_bytes: self.output_bytes,
+            last_output_step: self.last_output_step,
+        });
         match self
             .program
             .tokens
@@ -290,48 +1831,152 @@ impl<R: Read> Interpreter<R> {
             .ok_or(InterpreterError::InvalidProgram)?
         {
             Token::Increment(value) => {
-                self.tape[self.ptr] = self.tape[self.ptr].wrapping_add(*value)
+                let old = self.tape[self.ptr];
+                self.tape[self.ptr] = self.add_to_cell(old, *value);
+                self.record_change(old)?;
             }
             Token::Move(value) => {
-                if self.ptr.wrapping_add(*value as usize) >= self.tape.len() {
+                if self.ring_tape {
+                    let offset = value.rem_euclid(self.tape.len() as isize) as usize;
+                    self.ptr = (self.ptr + offset) % self.tape.len();
+                } else if self.ptr.wrapping_add(*value as usize) >= self.tape.len() {
                     return Err(InterpreterError::TapeOverrun);
+                } else {
+                    self.ptr = self.ptr.wrapping_add(*value as usize);
                 }
-                self.ptr = self.ptr.wrapping_add(*value as usize);
+                self.max_ptr = self.max_ptr.max(self.ptr);
+                self.travel += value.unsigned_abs() as u64;
             }
             Token::JumpZero(value) => {
                 if self.tape[self.ptr] == 0 {
                     self.pc = *value - 1
+                } else {
+                    self.loop_stack.push(self.pc);
                 }
             }
             Token::JumpNotZero(value) => {
                 if self.tape[self.ptr] != 0 {
                     self.pc = *value - 1
+                } else {
+                    self.loop_stack.pop();
                 }
             }
+            Token::Output if self.output_gate.is_some_and(|gate| self.tape[gate] == 0) => (),
             Token::Output => {
-                print!("{}", self.tape[self.ptr] as char);
-                std::io::stdout().flush().unwrap();
+                let cell = self.tape[self.ptr];
+                let cell_bytes = self.cell_width.bits() as usize / 8;
+                let mut buffer = [0; 4];
+                let bytes: &[u8] = match (self.wide_output, self.output_endianness) {
+                    (WideOutput::LowByte, _) => &[cell as u8],
+                    (WideOutput::AllBytes, Endianness::Little) => {
+                        buffer = cell.to_le_bytes();
+                        &buffer[..cell_bytes]
+                    }
+                    (WideOutput::AllBytes, Endianness::Big) => {
+                        buffer = cell.to_be_bytes();
+                        &buffer[4 - cell_bytes..]
+                    }
+                    // Values that aren't code points, such as surrogates, can't be encoded
+                    (WideOutput::Unicode, _) => char::from_u32(cell)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                        .encode_utf8(&mut buffer)
+                        .as_bytes(),
+                };
+                if let Some(max_output) = self.max_output
+                    && self.output_bytes + bytes.len() as u64 > max_output
+                {
+                    return Err(InterpreterError::OutputLimitExceeded(max_output));
+                }
+                if let Some(captured) = &mut self.captured_output {
+                    captured.extend_from_slice(bytes);
+                } else {
+                    self.output
+                        .write_all(bytes)
+                        .map_err(|_| InterpreterError::OutputError)?;
+                    if !self.buffered_output {
+                        self.flush_output()?;
+                    }
+                }
+                self.output_bytes += bytes.len() as u64;
+                self.last_output_step = self.steps + 1;
             }
             Token::Input => {
+                // Prompts written before the input is read must be visible
+                if self.buffered_output {
+                    self.flush_output()?;
+                }
+                let old = self.tape[self.ptr];
                 let mut buffer = [0u8; 1];
                 let mut bytes = self.input.read(&mut buffer);
                 if buffer[0] == b'\r' {
                     bytes = self.input.read(&mut buffer); // skip carriage return
                 }
+                if let Err(e) = &bytes
+                    && e.kind() == ErrorKind::WouldBlock
+                {
+                    return Err(InterpreterError::InputWouldBlock);
+                }
+                self.trace_instruction()?;
                 match bytes {
-                    Ok(0) => match self.eof_behaviour {
-                        EofBehaviour::SetZero => self.tape[self.ptr] = 0,
-                        EofBehaviour::SetMinusOne => self.tape[self.ptr] = 255,
-                        EofBehaviour::DontSet => (),
-                    },
-                    Ok(_) => self.tape[self.ptr] = buffer[0],
+                    Ok(0) => {
+                        match self.eof_behaviour {
+                            EofBehaviour::SetZero => self.tape[self.ptr] = 0,
+                            EofBehaviour::SetMinusOne => {
+                                self.tape[self.ptr] = self.cell_width.max()
+                            }
+                            EofBehaviour::DontSet => (),
+                            EofBehaviour::SetValue(value) => self.tape[self.ptr] = value as u32,
+                        }
+                        if self.eof_behaviour != EofBehaviour::DontSet {
+                            self.record_change(old)?;
+                        }
+                    }
+                    Ok(_) => {
+                        self.tape[self.ptr] = buffer[0] as u32;
+                        self.input_bytes += 1;
+                        if let Some(consumed) = &mut self.consumed_input {
+                            consumed.push(buffer[0]);
+                        }
+                        self.record_change(old)?;
+                    }
                     Err(_) => return Err(InterpreterError::InputError),
                 }
             }
-            Token::PrintState => self.print_state(),
-            Token::Eof => return Ok(false),
+            Token::PrintState => {
+                if self.buffered_output {
+                    self.flush_output()?;
+                }
+                self.print_state_command()
+            }
+            Token::Eof => {
+                self.flush_collapsed_states();
+                self.flush_output()?;
+                return Ok(false);
+            }
         }
         self.pc += 1;
+        self.steps += 1;
+        if let Some(counts) = &mut self.pc_counts {
+            counts[executed] += 1;
+        }
+        if let (Some(log), Some(undo)) = (&mut self.undo_log, undo) {
+            log.push(undo);
+        }
+
+        if let Some(profile) = &mut self.loop_profile {
+            match profile.get_mut(self.loop_stack.as_slice()) {
+                Some(steps) => *steps += 1,
+                None => {
+                    profile.insert(self.loop_stack.clone(), 1);
+                }
+            }
+        }
+
+        if let Some(watchdog) = self.output_watchdog
+            && self.steps - self.last_output_step >= watchdog
+        {
+            return Err(InterpreterError::OutputWatchdog(watchdog));
+        }
 
         while !(self.program.units[self.current_unit].start
             ..self.program.units[self.current_unit].end)
@@ -344,6 +1989,20 @@ impl<R: Read> Interpreter<R> {
         Ok(true)
     }
 
+    /// Takes a single step in the interpreter without blocking on input. This is opt-in: it only
+    /// avoids blocking if the input reader is non-blocking, i.e. returns
+    /// `std::io::ErrorKind::WouldBlock` when no input is available. In that case, if the current
+    /// instruction is an input, `StepOutcome::NeedInput` is returned and nothing is executed. With
+    /// a blocking reader, this behaves exactly like `Interpreter::step`.
+    pub fn try_step(&mut self) -> Result<StepOutcome, InterpreterError> {
+        match self.step() {
+            Ok(true) => Ok(StepOutcome::Continue),
+            Ok(false) => Ok(StepOutcome::Halted),
+            Err(InterpreterError::InputWouldBlock) => Ok(StepOutcome::NeedInput),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Steps instructions until the current unit is left. Returns OK(true) if there's still more program to
     /// execute, and Ok(false) if the program has halted (reached EOF). May return an error if the
     /// brainfuck program tries to move outside the tape, or if IO fails
@@ -360,27 +2019,560 @@ impl<R: Read> Interpreter<R> {
 
     /// Runs the program until it halts (reached EOF).
     pub fn run(&mut self) -> Result<(), InterpreterError> {
-        while self.step()? {}
+        if self.delay.is_zero() {
+            while self.step()? {}
+        } else {
+            while self.step()? {
+                self.pace()?;
+            }
+        }
         Ok(())
     }
 
+    /// Runs the program until it halts like `Interpreter::run`, returning the bytes it output
+    /// instead of writing them to the output writer
+    pub fn run_capture(&mut self) -> Result<Vec<u8>, InterpreterError> {
+        self.captured_output = Some(Vec::new());
+        let result = self.run();
+        let captured = self.captured_output.take().unwrap_or_default();
+        result.map(|_| captured)
+    }
+
+    /// Runs the program until the next instruction is a print of the interpreter's state, without
+    /// executing it. Returns Ok(true) if a print was reached, and Ok(false) if the program has
+    /// halted.
+    pub fn run_to_print(&mut self) -> Result<bool, InterpreterError> {
+        while self.program.tokens[self.pc] != Token::PrintState {
+            if !self.step()? {
+                return Ok(false);
+            }
+            if !self.delay.is_zero() {
+                self.pace()?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs the program until an output instruction has written something, it halts (reached EOF)
+    /// or it hits a breakpoint. Output instructions silenced by the output gate don't count.
+    /// Returns Ok(true) if there's still more program to execute, and Ok(false) if the program
+    /// has halted.
+    pub fn run_to_output(&mut self) -> Result<bool, InterpreterError> {
+        let output_bytes = self.output_bytes;
+        loop {
+            if !self.step()? {
+                return Ok(false);
+            }
+            if self.output_bytes > output_bytes || self.at_breakpoint() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Runs the program until it halts (reached EOF) or until `interrupt` is set. Returns Ok(true)
+    /// if execution was interrupted, and Ok(false) if the program has halted. The flag is cleared
+    /// when an interrupt is acknowledged, so the interpreter can be handed over to the debugger
+    /// with its state intact.
+    pub fn run_interruptible(&mut self, interrupt: &AtomicBool) -> Result<bool, InterpreterError> {
+        while self.step()? {
+            if interrupt.swap(false, Ordering::Relaxed) {
+                return Ok(true);
+            }
+            if !self.delay.is_zero() {
+                self.pace()?;
+            }
+        }
+        Ok(false)
+    }
+
     /// Adds a breakpoint. Breakpoints are only considered in the `Interpreter::cont` function
     pub fn add_breakpoint(&mut self, breakpoint: usize) {
-        self.breakpoints.insert(breakpoint);
+        self.breakpoints.insert(
+            breakpoint,
+            Breakpoint {
+                enabled: true,
+                ignore_count: 0,
+            },
+        );
+    }
+
+    /// Adds a breakpoint at the start of every unit, returning how many units there are
+    pub fn break_all_units(&mut self) -> usize {
+        let starts: Vec<_> = self.program.units.iter().map(|unit| unit.start).collect();
+        for &start in &starts {
+            self.add_breakpoint(start);
+        }
+        starts.len()
+    }
+
+    /// Clears the breakpoints at the start of every unit, returning how many were removed
+    pub fn clear_all_units(&mut self) -> usize {
+        let starts: Vec<_> = self.program.units.iter().map(|unit| unit.start).collect();
+        starts
+            .into_iter()
+            .filter(|&start| self.clear_breakpoint(start))
+            .count()
     }
 
     /// Clears a breakpoint. Returns true if successful, returns false if no such breakpoint existed
     pub fn clear_breakpoint(&mut self, breakpoint: usize) -> bool {
-        self.breakpoints.remove(&breakpoint)
+        self.breakpoints.remove(&breakpoint).is_some()
+    }
+
+    /// Enables or disables a breakpoint. Returns false if no such breakpoint existed
+    pub fn set_breakpoint_enabled(&mut self, breakpoint: usize, enabled: bool) -> bool {
+        match self.breakpoints.get_mut(&breakpoint) {
+            Some(breakpoint) => {
+                breakpoint.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns every breakpoint along with its address, sorted by address
+    pub fn breakpoints(&self) -> Vec<(usize, &Breakpoint)> {
+        let mut breakpoints: Vec<_> = self
+            .breakpoints
+            .iter()
+            .map(|(&address, breakpoint)| (address, breakpoint))
+            .collect();
+        breakpoints.sort_by_key(|(address, _)| *address);
+        breakpoints
+    }
+
+    /// Sets how many upcoming hits a breakpoint skips before stopping execution. Returns false if
+    /// no such breakpoint existed
+    pub fn set_breakpoint_ignore_count(&mut self, breakpoint: usize, ignore_count: u64) -> bool {
+        match self.breakpoints.get_mut(&breakpoint) {
+            Some(breakpoint) => {
+                breakpoint.ignore_count = ignore_count;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a data breakpoint, which stops `Interpreter::cont` the moment the cell at `address`
+    /// starts satisfying the comparison with `value`. A comparison that already holds only
+    /// triggers once it stops and starts holding again.
+    pub fn add_data_breakpoint(&mut self, address: usize, comparison: Comparison, value: u32) {
+        let satisfied = comparison.holds(self.tape[address], value);
+        self.data_breakpoints.push(DataBreakpoint {
+            address,
+            comparison,
+            value,
+            satisfied,
+        });
+    }
+
+    /// Returns every data breakpoint, in the order they were added
+    pub fn data_breakpoints(&self) -> &[DataBreakpoint] {
+        &self.data_breakpoints
+    }
+
+    /// Removes every data breakpoint
+    pub fn clear_data_breakpoints(&mut self) {
+        self.data_breakpoints.clear();
+    }
+
+    /// Returns the data breakpoint that last stopped execution, if any, so it's only reported once
+    pub fn take_data_breakpoint_hit(&mut self) -> Option<&DataBreakpoint> {
+        self.data_breakpoint_hit
+            .take()
+            .map(|index| &self.data_breakpoints[index])
+    }
+
+    /// Adds a watch expression, which stops `Interpreter::cont` whenever the little-endian integer
+    /// held in the `len` cells starting at `address` changes. The region must fit in the tape, and
+    /// span 1 to 64 bits worth of cells.
+    pub fn add_watch_expression(&mut self, name: String, address: usize, len: usize) {
+        let value = self.read_le(address, len);
+        self.watch_expressions.push(WatchExpression {
+            name,
+            address,
+            len,
+            value,
+        });
+    }
+
+    /// Returns every watch expression, in the order they were added
+    pub fn watch_expressions(&self) -> &[WatchExpression] {
+        &self.watch_expressions
+    }
+
+    /// Removes every watch expression
+    pub fn clear_watch_expressions(&mut self) {
+        self.watch_expressions.clear();
+    }
+
+    /// Returns the watch expression that last stopped execution, if any, along with its value
+    /// before the change, so it's only reported once
+    pub fn take_watch_hit(&mut self) -> Option<(&WatchExpression, u64)> {
+        self.watch_hit
+            .take()
+            .map(|(index, old)| (&self.watch_expressions[index], old))
+    }
+
+    /// Reads `len` cells starting at `address` as a little-endian integer
+    fn read_le(&self, address: usize, len: usize) -> u64 {
+        let bits = self.cell_width.bits();
+        self.tape[address..address + len]
+            .iter()
+            .rev()
+            .fold(0, |value: u64, &cell| {
+                value.wrapping_shl(bits) | cell as u64
+            })
+    }
+
+    /// Makes a range of cells read-only, so that `Interpreter::cont` stops after any write to them
+    pub fn protect(&mut self, range: Range<usize>) {
+        self.protected.push(range);
+    }
+
+    /// Protects every cell that currently isn't zero, returning the protected ranges. This is meant
+    /// to be used once a program's setup phase has filled in data that shouldn't change anymore.
+    pub fn freeze(&mut self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for (address, &cell) in self.tape.iter().enumerate() {
+            match (start, cell) {
+                (None, 1..) => start = Some(address),
+                (Some(range_start), 0) => {
+                    ranges.push(range_start..address);
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(range_start) = start {
+            ranges.push(range_start..self.tape.len());
+        }
+        self.protected.extend(ranges.iter().cloned());
+        ranges
+    }
+
+    /// Removes every protection from the tape
+    pub fn unprotect_all(&mut self) {
+        self.protected.clear();
+    }
+
+    /// Returns the write to a protected cell that last stopped execution, if any, so it's only
+    /// reported once
+    pub fn take_protection_hit(&mut self) -> Option<CellChange> {
+        self.protection_hit.take()
+    }
+
+    /// Returns true if an enabled breakpoint at the current instruction, a data breakpoint, or a
+    /// write to a protected cell should stop execution. Hits of breakpoints that still have an
+    /// ignore count are skipped, decrementing the count.
+    fn at_breakpoint(&mut self) -> bool {
+        // Data breakpoints are checked after every step, so skip them entirely when there are none
+        if !self.data_breakpoints.is_empty() {
+            for (index, breakpoint) in self.data_breakpoints.iter_mut().enumerate() {
+                let satisfied = breakpoint
+                    .comparison
+                    .holds(self.tape[breakpoint.address], breakpoint.value);
+                if satisfied && !breakpoint.satisfied && self.data_breakpoint_hit.is_none() {
+                    self.data_breakpoint_hit = Some(index);
+                }
+                breakpoint.satisfied = satisfied;
+            }
+            if self.data_breakpoint_hit.is_some() {
+                return true;
+            }
+        }
+        if !self.watch_expressions.is_empty() {
+            for index in 0..self.watch_expressions.len() {
+                let watch = &self.watch_expressions[index];
+                let value = self.read_le(watch.address, watch.len);
+                if value != watch.value {
+                    if self.watch_hit.is_none() {
+                        self.watch_hit = Some((index, watch.value));
+                    }
+                    self.watch_expressions[index].value = value;
+                }
+            }
+            if self.watch_hit.is_some() {
+                return true;
+            }
+        }
+        if self.protection_hit.is_some() {
+            return true;
+        }
+
+        match self.breakpoints.get_mut(&self.pc) {
+            Some(breakpoint) if breakpoint.enabled => {
+                if breakpoint.ignore_count > 0 {
+                    breakpoint.ignore_count -= 1;
+                    false
+                } else {
+                    true
+                }
+            }
+            _ => false,
+        }
     }
 
     /// Runs the program until it halts (reached EOF) or until it hits a breakpoint.
     pub fn cont(&mut self) -> Result<bool, InterpreterError> {
         while self.step()? {
-            if self.breakpoints.contains(&self.pc) {
+            if self.at_breakpoint() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Like `Interpreter::cont`, but gives up after executing `max_steps` instructions. Returns
+    /// Ok(None) if it gave up, or else what `Interpreter::cont` would have returned.
+    pub fn cont_for(&mut self, max_steps: u64) -> Result<Option<bool>, InterpreterError> {
+        let limit = self.steps.saturating_add(max_steps);
+        while self.step()? {
+            if self.at_breakpoint() {
+                return Ok(Some(true));
+            }
+            if self.steps >= limit {
+                return Ok(None);
+            }
+        }
+        Ok(Some(false))
+    }
+
+    /// Runs the program, ignoring breakpoints, until execution enters the given unit or it halts
+    /// (reached EOF). Returns Ok(true) if there's still more program to execute, and Ok(false) if
+    /// the program has halted.
+    pub fn run_to_unit(&mut self, unit: usize) -> Result<bool, InterpreterError> {
+        let mut previous_unit = self.current_unit;
+        while self.step()? {
+            if self.current_unit == unit && previous_unit != unit {
+                return Ok(true);
+            }
+            previous_unit = self.current_unit;
+        }
+        Ok(false)
+    }
+
+    /// Returns the addresses of the `[` of every loop currently being executed, innermost last
+    pub fn loop_stack(&self) -> &[usize] {
+        &self.loop_stack
+    }
+
+    /// Runs the program until the innermost loop being executed is exited, it halts (reached EOF)
+    /// or it hits a breakpoint. Returns Ok(true) if there's still more program to execute, and
+    /// Ok(false) if the program has halted. If no loop is being executed, runs until a breakpoint
+    /// or halt, like `Interpreter::cont`.
+    pub fn finish(&mut self) -> Result<bool, InterpreterError> {
+        let depth = self.loop_stack.len();
+        while self.step()? {
+            if self.loop_stack.len() < depth || self.at_breakpoint() {
                 return Ok(true);
             }
         }
         Ok(false)
     }
 }
+
+/// Returns the character a cell is displayed as next to its value, or a dot if it isn't in the
+/// printable range
+fn display_char(value: u32, printable: &RangeInclusive<u8>) -> char {
+    match u8::try_from(value) {
+        Ok(value) if printable.contains(&value) => value as char,
+        _ => '·',
+    }
+}
+
+/// Counts how many cells hold each value
+fn value_counts(cells: &[u32]) -> HashMap<u32, usize> {
+    let mut counts = HashMap::new();
+    for &cell in cells {
+        *counts.entry(cell).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Lints;
+    use std::io::BufReader;
+
+    type TestInterpreter = Interpreter<&'static [u8], Vec<u8>>;
+
+    /// Creates an interpreter for `source` with a tape of `tape_size` cells filled with
+    /// `fill_byte`, and no input
+    fn interpreter(source: &str, tape_size: usize, fill_byte: u8) -> TestInterpreter {
+        let program = Program::parse(
+            BufReader::new(source.as_bytes()),
+            false,
+            Lints::default(),
+            None,
+        )
+        .unwrap();
+        Interpreter::new(
+            program,
+            tape_size,
+            fill_byte,
+            EofBehaviour::DontSet,
+            &[],
+            Vec::new(),
+        )
+    }
+
+    /// Runs `source` on a 16 cell tape filled with zeroes, with no input
+    fn run(source: &str, configure: impl FnOnce(&mut TestInterpreter)) -> Vec<u32> {
+        let mut interpreter = interpreter(source, 16, 0);
+        configure(&mut interpreter);
+        interpreter.run().unwrap();
+        interpreter.tape().to_vec()
+    }
+
+    fn digits(interpreter: &mut TestInterpreter) {
+        interpreter.set_cell_range(Some(0..=9));
+    }
+
+    #[test]
+    fn cell_range_wraps_at_the_top() {
+        assert_eq!(run("+++++++++", digits)[0], 9);
+        assert_eq!(run("++++++++++", digits)[0], 0);
+        assert_eq!(run("+++++++++++", digits)[0], 1);
+    }
+
+    #[test]
+    fn cell_range_wraps_at_the_bottom() {
+        assert_eq!(run("-", digits)[0], 9);
+        assert_eq!(run("--", digits)[0], 8);
+        assert_eq!(run("+-", digits)[0], 0);
+    }
+
+    #[test]
+    fn cell_range_wraps_with_an_offset_range() {
+        let starting_at = |value| {
+            move |interpreter: &mut TestInterpreter| {
+                interpreter.set_cell_range(Some(0x41..=0x43));
+                interpreter.write_cells(0, &[value]);
+            }
+        };
+        assert_eq!(run("+", starting_at(0x43))[0], 0x41);
+        assert_eq!(run("-", starting_at(0x41))[0], 0x43);
+        assert_eq!(run("++++", starting_at(0x42))[0], 0x43);
+    }
+
+    #[test]
+    fn cell_range_wraps_folded_runs_longer_than_the_range() {
+        assert_eq!(run(&"+".repeat(256), digits)[0], 6);
+        assert_eq!(run(&"+".repeat(1000), digits)[0], 0);
+        assert_eq!(run(&"-".repeat(257), digits)[0], 3);
+    }
+
+    #[test]
+    fn print_tape_handles_a_short_last_line() {
+        // Both lines are printed, since neither is all zeroes
+        assert_eq!(interpreter("", 20, 1).print_tape_labeled(&[]), 2);
+        // A label on the short line keeps it from being skipped
+        assert_eq!(interpreter("", 20, 0).print_tape_labeled(&[(19, "end")]), 2);
+        interpreter("", 20, 0).print_tape();
+        interpreter("", 1000, 0xff).print_tape();
+    }
+
+    #[test]
+    fn display_char_hides_unprintable_bytes() {
+        let printable = interpreter("", 16, 0).printable;
+        assert_eq!(display_char(0x41, &printable), 'A');
+        assert_eq!(display_char(0x7e, &printable), '~');
+        assert_eq!(display_char(0x7f, &printable), '·');
+        assert_eq!(display_char(0xa0, &printable), '·');
+        assert_eq!(display_char(0x141, &printable), '·');
+    }
+
+    #[test]
+    fn display_char_uses_a_custom_printable_range() {
+        let mut interpreter = interpreter("", 16, 0);
+        interpreter.set_printable_range(0xa0..=0xff);
+        assert_eq!(display_char(0xa0, &interpreter.printable), '\u{a0}');
+        assert_eq!(display_char(0xe9, &interpreter.printable), 'é');
+        assert_eq!(display_char(0x41, &interpreter.printable), '·');
+    }
+
+    #[test]
+    fn run_to_output_skips_gated_output() {
+        let mut interpreter = interpreter("+.>+<.", 16, 0);
+        interpreter.set_output_gate(Some(1));
+        assert!(interpreter.run_to_output().unwrap());
+        assert_eq!(interpreter.output_bytes(), 1);
+        assert_eq!(interpreter.pc(), 6);
+        assert!(!interpreter.run_to_output().unwrap());
+        assert_eq!(interpreter.output_bytes(), 1);
+    }
+
+    #[test]
+    fn explain_describes_moves_off_the_tape() {
+        let mut interpreter = interpreter("<", 16, 0);
+        assert!(interpreter.explain().contains("which would leave the tape"));
+        interpreter.set_ring_tape(true);
+        assert!(
+            interpreter
+                .explain()
+                .contains("0x0 -> 0xf, wrapping around the ring tape")
+        );
+    }
+
+    #[test]
+    fn fork_stops_on_the_output_watchdog_like_the_live_session() {
+        let mut interpreter = interpreter(".+[]", 16, 0);
+        interpreter.set_output_watchdog(Some(10));
+        assert_eq!(interpreter.cont_for(5).unwrap(), None);
+        let mut fork = interpreter.fork();
+        let forked = fork.cont_for(100);
+        let live = interpreter.cont_for(100);
+        assert!(matches!(forked, Err(InterpreterError::OutputWatchdog(10))));
+        assert!(matches!(live, Err(InterpreterError::OutputWatchdog(10))));
+        assert_eq!(fork.steps(), interpreter.steps());
+    }
+
+    #[test]
+    fn input_that_would_block_is_not_traced() {
+        let program =
+            Program::parse(BufReader::new(&b"+,"[..]), false, Lints::default(), None).unwrap();
+        let mut interpreter = Interpreter::new(
+            program,
+            16,
+            0,
+            EofBehaviour::DontSet,
+            NoInput,
+            std::io::sink(),
+        );
+        let path = std::env::temp_dir().join(format!("brainstorm-trace-{}", std::process::id()));
+        interpreter.set_trace(Some(Box::new(std::fs::File::create(&path).unwrap())));
+        assert_eq!(interpreter.try_step().unwrap(), StepOutcome::Continue);
+        assert_eq!(interpreter.try_step().unwrap(), StepOutcome::NeedInput);
+        assert_eq!(interpreter.try_step().unwrap(), StepOutcome::NeedInput);
+        assert_eq!(interpreter.pc(), 1);
+        interpreter.flush_logs().unwrap();
+        let trace = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(trace.unwrap(), "0x0\n");
+    }
+
+    #[test]
+    fn step_back_restores_the_counters() {
+        let mut interpreter = interpreter("++[>+.<-]", 16, 0);
+        interpreter.set_undo_log(true);
+        interpreter.set_pc_profiling(true);
+        interpreter.set_loop_profiling(true);
+        interpreter.run().unwrap();
+        while interpreter.step_back() {}
+        assert_eq!(interpreter.steps(), 0);
+        assert_eq!(interpreter.max_ptr(), 0);
+        assert_eq!(interpreter.travel(), 0);
+        assert_eq!(interpreter.output_bytes(), 0);
+        assert!(
+            interpreter
+                .pc_counts()
+                .unwrap()
+                .iter()
+                .all(|&count| count == 0)
+        );
+        assert!(interpreter.loop_profile().is_empty());
+    }
+}
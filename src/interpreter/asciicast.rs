@@ -0,0 +1,120 @@
+use crate::interpreter::debugger::terminal_dimension;
+use std::cell::RefCell;
+use std::fmt::{Display, Write as _};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// A recording of a program's output with the time every write happened, which can be saved in
+/// the asciicast v2 format and replayed with asciinema.
+///
+/// The format is a JSON header with the terminal's dimensions on the first line, followed by one
+/// `[<seconds>, "o", <text>]` line per write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asciicast {
+    pub width: usize,
+    pub height: usize,
+    /// When the recording started, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The seconds since the recording started at which each run of bytes was written
+    pub events: Vec<(f64, Vec<u8>)>,
+}
+
+impl Asciicast {
+    /// Starts an empty recording, with the dimensions of the terminal it's started in
+    pub fn for_terminal() -> Asciicast {
+        Asciicast {
+            width: terminal_dimension("COLUMNS", "cols", 80),
+            height: terminal_dimension("LINES", "lines", 24),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            events: Vec::new(),
+        }
+    }
+
+    /// Appends bytes written at the given time. Bytes that complete a UTF-8 character started by
+    /// the last event are merged into it, since every event has to be valid text.
+    pub fn record(&mut self, time: f64, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        match self.events.last_mut() {
+            Some((_, last))
+                if let Err(e) = std::str::from_utf8(last)
+                    && e.error_len().is_none() =>
+            {
+                last.extend_from_slice(bytes)
+            }
+            _ => self.events.push((time, bytes.to_vec())),
+        }
+    }
+}
+
+impl Display for Asciicast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}",
+            self.width, self.height, self.timestamp
+        )?;
+        for (time, bytes) in &self.events {
+            writeln!(
+                f,
+                "[{time:.6}, \"o\", {}]",
+                json_string(&String::from_utf8_lossy(bytes))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a string as a JSON string literal
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Wraps a writer, recording every byte written to it in an asciicast along with when it was
+/// written
+pub struct AsciicastWriter<W: Write> {
+    inner: W,
+    cast: Rc<RefCell<Asciicast>>,
+    start: Instant,
+}
+
+impl<W: Write> AsciicastWriter<W> {
+    pub fn new(inner: W, cast: Rc<RefCell<Asciicast>>) -> AsciicastWriter<W> {
+        AsciicastWriter {
+            inner,
+            cast,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<W: Write> Write for AsciicastWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.cast
+            .borrow_mut()
+            .record(self.start.elapsed().as_secs_f64(), &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
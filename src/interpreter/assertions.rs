@@ -0,0 +1,118 @@
+use crate::interpreter::{Interpreter, InterpreterError};
+use crate::parser::Token;
+use std::fmt::Display;
+use std::io::Read;
+use thiserror::Error;
+
+/// A point in a program's execution at which an assertion is checked
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StopCondition {
+    /// Stops when the instruction at this address is next to execute
+    Pc(usize),
+    /// Stops once this many instructions have been executed
+    Step(u64),
+}
+
+impl Display for StopCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pc(pc) => write!(f, "pc {pc:#x}"),
+            Self::Step(step) => write!(f, "step {step}"),
+        }
+    }
+}
+
+/// A check on the interpreter's state
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Assertion {
+    /// The cell at `address` holds `value`
+    Cell { address: usize, value: u32 },
+    /// The pointer is at this address
+    Ptr(usize),
+    /// The program has output exactly these bytes so far
+    Output(Vec<u8>),
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cell { address, value } => write!(f, "cell {address:#x} = {value:#04x}"),
+            Self::Ptr(ptr) => write!(f, "pointer = {ptr:#x}"),
+            Self::Output(output) => write!(f, "output = \"{}\"", output.escape_ascii()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AssertionFailure {
+    #[error("check {index}: {condition} was never reached")]
+    NotReached {
+        index: usize,
+        condition: StopCondition,
+    },
+    #[error("check {index} at {condition}: expected {assertion}, but found {actual}")]
+    Failed {
+        index: usize,
+        condition: StopCondition,
+        assertion: Assertion,
+        actual: String,
+    },
+    #[error("check {index}: {error}")]
+    Interpreter {
+        index: usize,
+        error: InterpreterError,
+    },
+}
+
+impl<R: Read> Interpreter<R, Vec<u8>> {
+    /// Runs the program, stopping at each check's condition in order to evaluate its assertion.
+    /// Stops at the first failing check, or if the program halts or fails before a check's
+    /// condition is reached. The output must be captured in a `Vec<u8>` so it can be checked.
+    pub fn run_with_assertions(
+        &mut self,
+        checks: &[(StopCondition, Assertion)],
+    ) -> Result<(), AssertionFailure> {
+        for (index, (condition, assertion)) in checks.iter().enumerate() {
+            let not_reached = AssertionFailure::NotReached {
+                index,
+                condition: *condition,
+            };
+            loop {
+                let reached = match *condition {
+                    StopCondition::Pc(pc) => self.pc == pc,
+                    StopCondition::Step(step) if self.steps > step => return Err(not_reached),
+                    StopCondition::Step(step) => self.steps == step,
+                };
+                if reached {
+                    break;
+                }
+                if self.program.tokens[self.pc] == Token::Eof {
+                    return Err(not_reached);
+                }
+                self.step()
+                    .map_err(|error| AssertionFailure::Interpreter { index, error })?;
+            }
+
+            let actual = match assertion {
+                Assertion::Cell { address, value } => match self.tape.get(*address) {
+                    Some(cell) if cell == value => None,
+                    Some(cell) => Some(format!("{cell:#04x}")),
+                    None => Some("an address outside of the tape".to_string()),
+                },
+                Assertion::Ptr(ptr) => (self.ptr != *ptr).then(|| format!("{:#x}", self.ptr)),
+                Assertion::Output(output) => {
+                    (self.output != *output).then(|| format!("\"{}\"", self.output.escape_ascii()))
+                }
+            };
+            if let Some(actual) = actual {
+                return Err(AssertionFailure::Failed {
+                    index,
+                    condition: *condition,
+                    assertion: assertion.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
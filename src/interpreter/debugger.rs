@@ -1,21 +1,102 @@
-use crate::interpreter::Interpreter;
+use crate::interpreter::repro::Repro;
+use crate::interpreter::{
+    AddressMode, CellWidth, Checkpoint, Comparison, EofBehaviour, Interpreter, InterpreterError,
+};
+use crate::parser::{Lints, Program, Token};
 use owo_colors::OwoColorize;
+use std::collections::{BTreeMap, VecDeque};
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufReader, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-pub struct Debugger<T: Read> {
-    interpreter: Interpreter<T>,
+/// How many of the most recent cell changes the debugger keeps for the `changed` command
+const CHANGE_HISTORY_SIZE: usize = 64;
+
+pub struct Debugger<T: Read, W: Write> {
+    interpreter: Interpreter<T, W>,
     running: bool,
+    halt_summary: bool,
+    /// Names given to tape addresses with the `bookmark` command
+    bookmarks: BTreeMap<String, usize>,
+    /// States saved with the `checkpoint` command, and whether the program was running in them
+    checkpoints: BTreeMap<String, (Checkpoint, bool)>,
+    /// The command line the session was started with, for the `repro` command
+    invocation: Vec<String>,
+    /// The program's source as it was read, for the `repro` command
+    source: Option<Vec<u8>>,
+    /// Every command entered so far, for the `repro` command
+    history: Vec<String>,
+    /// Commands to run before reading any from stdin
+    script: VecDeque<String>,
+    /// Whether the context window is only printed after a motion when it enters another unit
+    ctx_on_unit_change: bool,
+    /// The unit the interpreter was in when the context was last printed after a motion
+    last_unit: usize,
+    /// Set on Ctrl-C, to stop the `watch-run` command
+    interrupt: Arc<AtomicBool>,
+    /// Whether the program reads its input from stdin, which `watch-run` then can't also read
+    /// key presses from
+    program_reads_stdin: bool,
 }
 
-impl<T: Read> Debugger<T> {
-    pub fn new(interpreter: Interpreter<T>) -> Debugger<T> {
+impl<T: Read, W: Write> Debugger<T, W> {
+    pub fn new(mut interpreter: Interpreter<T, W>) -> Debugger<T, W> {
+        interpreter.set_change_history_size(CHANGE_HISTORY_SIZE);
+        interpreter.set_pc_profiling(true);
+        interpreter.set_input_recording(true);
         Debugger {
+            last_unit: interpreter.current_unit(),
             interpreter,
             running: true,
+            halt_summary: false,
+            bookmarks: BTreeMap::new(),
+            checkpoints: BTreeMap::new(),
+            invocation: Vec::new(),
+            source: None,
+            history: Vec::new(),
+            script: VecDeque::new(),
+            ctx_on_unit_change: false,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            program_reads_stdin: false,
         }
     }
 
+    /// Sets the flag that stops the `watch-run` command, which should be set on Ctrl-C
+    pub fn set_interrupt(&mut self, interrupt: Arc<AtomicBool>) {
+        self.interrupt = interrupt;
+    }
+
+    /// Sets whether the program reads its input from stdin. If it does, `watch-run` leaves stdin
+    /// to the program and is only stopped with Ctrl-C.
+    pub fn set_program_reads_stdin(&mut self, program_reads_stdin: bool) {
+        self.program_reads_stdin = program_reads_stdin;
+    }
+
+    /// Sets the command line the session was started with, which the `repro` command saves so the
+    /// session can be reproduced with the same options
+    pub fn set_invocation(&mut self, invocation: Vec<String>) {
+        self.invocation = invocation;
+    }
+
+    /// Sets the program's source as it was read, which the `repro` command saves so the session is
+    /// reproduced with exactly the same program. Without it, the program is saved formatted.
+    pub fn set_source(&mut self, source: Vec<u8>) {
+        self.source = Some(source);
+    }
+
+    /// Sets commands to run, as if entered in order, before reading any from stdin
+    pub fn set_script(&mut self, commands: Vec<String>) {
+        self.script = commands.into();
+    }
+
+    /// Returns the interpreter being debugged
+    pub fn interpreter(&self) -> &Interpreter<T, W> {
+        &self.interpreter
+    }
+
     pub fn run(&mut self) {
         let mut last_command;
         let mut input = String::new();
@@ -31,14 +112,22 @@ impl<T: Read> Debugger<T> {
 
             last_command = input.clone();
             input.clear();
-            io::stdin().read_line(&mut input).unwrap(); // TODO handle this unwrap
+            if let Some(command) = self.script.pop_front() {
+                println!("{command}");
+                input = command;
+            } else {
+                io::stdin().read_line(&mut input).unwrap(); // TODO handle this unwrap
+            }
 
-            input = input.trim().to_lowercase();
+            input = input.trim().to_string();
             if input.is_empty() {
                 input = last_command.clone();
             }
 
             let l = input.trim().to_lowercase();
+            if l.split_whitespace().next() != Some("repro") {
+                self.history.push(input.clone());
+            }
             if match l.split_whitespace().next().unwrap() {
                 "h" | "help" => self.help(),
                 "q" | "quit" => {
@@ -47,18 +136,62 @@ impl<T: Read> Debugger<T> {
                 }
                 "ctx" | "context" => self.context(),
                 "p" | "program" => self.program(),
+                "tree" => self.tree(&l),
                 "t" | "tape" => self.tape(),
+                "u" | "units" => self.units(),
                 "n" | "next" => self.n(),
                 "ni" | "next-instruction" => self.ni(),
                 "b" | "break" => self.breakpoint(&l),
                 "cl" | "clear" => self.clear(&l),
+                "break-when" => self.break_when(&l),
+                "break-all-units" => self.break_all_units(),
+                "clear-all-units" => self.clear_all_units(),
+                "watch-expr" => self.watch_expr(&l),
+                "status" => self.status(),
+                "freeze" => self.freeze(),
+                "thaw" => self.thaw(),
                 "c" | "continue" => self.cont(),
+                "watch-run" => self.watch_run(&l),
+                "f" | "finish" => self.finish(),
+                "no" | "next-output" => self.next_output(),
+                "rtu" | "run-to-unit" => self.run_to_unit(&l),
+                "set-halt-summary" => self.set_halt_summary(&l),
+                "set-ctx-on-unit-change" => self.set_ctx_on_unit_change(&l),
+                "set-jump-annotations" => self.set_jump_annotations(&l),
+                "e" | "explain" => self.explain(),
+                "analyze-loop" => self.analyze_loop(),
+                "i" | "info" => self.info(&l),
+                "bl" | "breakpoints" => self.list_breakpoints(),
+                "d" | "delete" => self.delete(&l),
+                "enable" => self.set_enabled(&l, true),
+                "disable" => self.set_enabled(&l, false),
+                "hwm" => self.hwm(),
+                "travel" => self.travel(),
+                "sv" | "stack-view" => self.stack_view(&l),
+                "hash" => self.hash(),
+                "set-eof" => self.set_eof(&l),
+                "set-addr-mode" => self.set_addr_mode(&l),
+                "changed" => self.changed(&l),
+                "bookmark" => self.bookmark(&l),
+                "bookmarks" => self.list_bookmarks(),
+                "checkpoint" => self.checkpoint(&l),
+                "restore" => self.restore(&l),
+                "list-checkpoints" => self.list_checkpoints(),
+                "x" | "examine" => self.examine(&l),
+                "summary" => self.summary(),
+                "stats-tape" => self.stats_tape(),
+                "asm" => self.asm(&l),
+                "eval" => self.eval(&l),
+                "preview-cont" => self.preview_cont(),
+                "dump-c-array" => self.dump_c_array(&l),
+                "save-snapshot" => self.save_snapshot(&input),
+                "repro" => self.repro(&input),
                 _ => {
                     println!("Unknown command: {l}");
                     false
                 }
             } {
-                self.context();
+                self.motion_context();
             }
         }
     }
@@ -69,12 +202,114 @@ impl<T: Read> Debugger<T> {
         println!("  - q / quit - quits the debugger");
         println!("  - ctx / context - prints the context window");
         println!("  - p / program - prints the entire program units");
+        println!(
+            "  - tree - prints the program as an indented tree of loops, or only the current unit with `tree unit`"
+        );
         println!("  - t / tape - prints the tape");
+        println!("  - u / units - lists the program's units, marking those with breakpoints");
         println!("  - n / next - steps the interpreter by one unit");
         println!("  - ni / next-instruction - steps the interpreter by one bf instruction");
-        println!("  - b / break - set a breakpoint at the specified location (hex)");
-        println!("  - cl / clear - clear a breakpoint at the specified location (hex)");
+        println!(
+            "  - b / break - set a breakpoint at the specified location (hex, or line:column in source address mode), optionally followed by `ignore <count>` to skip its next hits"
+        );
+        println!("  - cl / clear - clear a breakpoint at the specified location");
+        println!("  - break-all-units - set a breakpoint at the start of every unit");
+        println!("  - clear-all-units - clear the breakpoints at the start of every unit");
+        println!(
+            "  - break-when - stop once a cell starts satisfying a comparison, as in `break-when <address> <==|!=|<|>> <value>` (hex), list them with no arguments, or remove them with `break-when clear`"
+        );
+        println!(
+            "  - watch-expr - stop whenever a region of cells read as a little-endian integer changes, as in `watch-expr <name> <address (hex)>:<length>`, list them with no arguments, or remove them with `watch-expr clear`"
+        );
+        println!(
+            "  - status - shows each data breakpoint's cell against its target, and the current value of each watch expression"
+        );
         println!("  - c / continue - continue execution until breakpoint or halt");
+        println!(
+            "  - watch-run - steps until breakpoint or halt while redrawing the tape, optionally with the delay between steps in milliseconds (defaults to 100), until a key is pressed or Ctrl-C. Only Ctrl-C stops it when the program reads its input from stdin"
+        );
+        println!(
+            "  - preview-cont - reports where continuing would stop, without changing the session"
+        );
+        println!("  - f / finish - continue execution until the current loop exits");
+        println!(
+            "  - no / next-output - continue execution until the next byte is output, and show where it came from"
+        );
+        println!(
+            "  - rtu / run-to-unit - continue execution, ignoring breakpoints, until the named unit is entered"
+        );
+        println!("  - e / explain - describes what the current instruction does");
+        println!(
+            "  - analyze-loop - describes the net effect of the loop at or around the current instruction, if its body only adds and moves without moving overall"
+        );
+        println!(
+            "  - i / info - prints the registers and an execution summary, or how many instructions of each kind ran with `info opcodes`"
+        );
+        println!("  - bl / breakpoints / info break - lists every breakpoint with its index");
+        println!("  - d / delete - delete the breakpoint with the specified index");
+        println!(
+            "  - enable / disable - enable or disable the breakpoint with the specified index"
+        );
+        println!("  - hwm - prints the highest tape address the pointer has reached");
+        println!(
+            "  - travel - prints the total distance the pointer has moved, and its average per step"
+        );
+        println!(
+            "  - sv / stack-view - prints the cells within the specified distance (default 8) of the pointer as a vertical stack"
+        );
+        println!(
+            "  - set-ctx-on-unit-change - with `on`, steps only print the current instruction, and the full context window once they enter another unit"
+        );
+        println!(
+            "  - set-jump-annotations - with `off`, brackets in the program and context views no longer show their target"
+        );
+        println!(
+            "  - set-addr-mode - shows and reads instruction addresses as token indices (token) or source line:column positions (source)"
+        );
+        println!(
+            "  - set-eof - sets what input past its end does: zero, minus-one, dont-set, or a value (hex) to set the cell to"
+        );
+        println!(
+            "  - changed - lists the most recently changed cells (default 10, at most 64), newest first, with the instruction that changed them"
+        );
+        println!(
+            "  - freeze - makes every non-zero cell read-only, stopping execution when one is written to"
+        );
+        println!("  - thaw - removes every read-only protection from the tape");
+        println!("  - bookmark - names a tape address (hex), as in `bookmark counter 40`");
+        println!("  - bookmarks - lists every bookmarked tape address");
+        println!(
+            "  - checkpoint - saves the tape, registers and loops under a name, to return to them with `restore <name>`"
+        );
+        println!(
+            "  - restore - returns to a checkpoint, although input read and output written since stay consumed"
+        );
+        println!("  - list-checkpoints - lists every checkpoint, with its step count and PC");
+        println!(
+            "  - x / examine - prints the cell at the specified tape address (hex) or bookmark (@name)"
+        );
+        println!(
+            "  - summary - prints a table summarizing the tape: non-zero cells, their range, the pointer, the most frequent values and the stats from `stats-tape`"
+        );
+        println!(
+            "  - stats-tape - prints the entropy, number of distinct values and run-length encoded size of the region between the lowest and highest non-zero cells"
+        );
+        println!(
+            "  - asm - appends brainfuck code to the end of the program, resuming it if it had halted"
+        );
+        println!(
+            "  - dump-c-array - prints a tape region as a C array, as in `dump-c-array <address (hex)> <count> <name>`"
+        );
+        println!(
+            "  - eval - runs brainfuck code on a copy of the tape, reporting the cell under the pointer and any output, without changing the session"
+        );
+        println!(
+            "  - save-snapshot - saves the tape, registers and breakpoints to the specified file, to compare with `--diff-snapshots`"
+        );
+        println!(
+            "  - repro - saves the program, command line, input read and commands entered to the specified file, to reproduce the session with `--repro`"
+        );
+        println!("  - hash - prints a hash of the tape, to compare memory states across runs");
         false
     }
 
@@ -84,76 +319,920 @@ impl<T: Read> Debugger<T> {
         false
     }
 
+    fn analyze_loop(&self) -> bool {
+        let program = self.interpreter.program();
+        let pc = self.interpreter.pc();
+        // The pointer is only known to be on the loop's cell when it's about to be tested
+        let (start, ptr) = match program.tokens[pc] {
+            Token::JumpZero(_) => (pc, Some(self.interpreter.ptr())),
+            _ => match self.interpreter.loop_stack().last() {
+                Some(&start) => (start, None),
+                None => {
+                    println!("Not in a loop");
+                    return false;
+                }
+            },
+        };
+        let Some(effect) = program.simple_loop_effect(start) else {
+            println!("Not a simple loop");
+            return false;
+        };
+
+        let cell = |offset: isize| match ptr {
+            Some(ptr) => format!("cell {offset:+} ({:#x})", ptr.wrapping_add_signed(offset)),
+            None => format!("cell {offset:+}"),
+        };
+        println!(
+            "Loop {}..{}:",
+            self.interpreter.format_address(effect.start),
+            self.interpreter.format_address(effect.end)
+        );
+        println!("  zeroes {}", cell(0));
+        for (offset, multiplier) in effect.multipliers() {
+            let factor = match multiplier.unsigned_abs() {
+                1 => String::new(),
+                factor => format!("{factor} * "),
+            };
+            if multiplier > 0 {
+                println!("  adds {factor}{} to {}", cell(0), cell(offset));
+            } else {
+                println!("  subtracts {factor}{} from {}", cell(0), cell(offset));
+            }
+        }
+        if let Some(ptr) = ptr {
+            let value = self.interpreter.tape()[ptr];
+            let direction = effect.deltas[&0].wrapping_neg() as u32;
+            let iterations = value.wrapping_mul(direction) & self.interpreter.cell_width().max();
+            println!("From here, it runs {iterations} times");
+        }
+        false
+    }
+
+    /// Prints the context after a motion, or only the current instruction if the context should
+    /// only be printed on unit changes and the unit is the same as last time
+    fn motion_context(&mut self) {
+        let unit = self.interpreter.current_unit();
+        if self.ctx_on_unit_change && self.running && unit == self.last_unit {
+            println!("{}", self.interpreter.current_instruction());
+        } else {
+            self.context();
+        }
+        self.last_unit = unit;
+    }
+
+    fn explain(&self) -> bool {
+        println!("{}", self.interpreter.explain());
+        false
+    }
+
+    fn info(&self, l: &str) -> bool {
+        match l.split_whitespace().nth(1) {
+            Some("b" | "break" | "breakpoints") => self.list_breakpoints(),
+            Some("opcodes") => {
+                self.interpreter.print_opcode_stats();
+                false
+            }
+            _ => {
+                self.interpreter.print_registers();
+                false
+            }
+        }
+    }
+
+    fn hwm(&self) -> bool {
+        println!("Max pointer: {:#x}", self.interpreter.max_ptr());
+        false
+    }
+
+    fn travel(&self) -> bool {
+        let travel = self.interpreter.travel();
+        let steps = self.interpreter.steps().max(1);
+        println!(
+            "Pointer travel: {travel} cells ({:.2} per step)",
+            travel as f64 / steps as f64
+        );
+        false
+    }
+
+    fn set_addr_mode(&mut self, l: &str) -> bool {
+        let address_mode = match l.split_whitespace().nth(1) {
+            Some("token") => AddressMode::Token,
+            Some("source") if self.interpreter.program().positions.is_empty() => {
+                println!("The program has no source positions");
+                return false;
+            }
+            Some("source") => AddressMode::Source,
+            _ => {
+                println!("Expected token or source");
+                return false;
+            }
+        };
+        self.interpreter.set_address_mode(address_mode);
+        println!("Address mode: {}", l.split_whitespace().nth(1).unwrap());
+        true
+    }
+
+    /// Parses an instruction address as entered in the current address mode: hex token indices,
+    /// or `line:column` (or just `line`) source positions
+    fn parse_location(&self, s: &str) -> Option<usize> {
+        match self.interpreter.address_mode() {
+            AddressMode::Token => usize::from_str_radix(s.trim_start_matches("0x"), 16).ok(),
+            AddressMode::Source => {
+                let (line, column) = match s.split_once(':') {
+                    Some((line, column)) => (line.parse().ok()?, Some(column.parse().ok()?)),
+                    None => (s.parse().ok()?, None),
+                };
+                self.interpreter.address_at_source(line, column)
+            }
+        }
+    }
+
+    fn set_eof(&mut self, l: &str) -> bool {
+        let eof_behaviour = match l.split_whitespace().nth(1) {
+            Some("zero") => EofBehaviour::SetZero,
+            Some("minus-one") => EofBehaviour::SetMinusOne,
+            Some("dont-set") => EofBehaviour::DontSet,
+            Some(value)
+                if let Ok(value) = u8::from_str_radix(value.trim_start_matches("0x"), 16) =>
+            {
+                EofBehaviour::SetValue(value)
+            }
+            _ => {
+                println!("Expected zero, minus-one, dont-set or a value (hex)");
+                return false;
+            }
+        };
+        self.interpreter.set_eof_behaviour(eof_behaviour);
+        println!("EOF behaviour: {eof_behaviour}");
+        false
+    }
+
+    fn changed(&self, l: &str) -> bool {
+        let count = match l.split_whitespace().nth(1) {
+            None => 10,
+            Some(count) if let Ok(count) = count.parse() => count,
+            _ => {
+                println!("Invalid count");
+                return false;
+            }
+        };
+        let history = self.interpreter.change_history();
+        if history.is_empty() {
+            println!("No cells have changed yet");
+        }
+        for change in history.iter().rev().take(count) {
+            println!(
+                "  {:#06x} = {:#04x}  {} {:#x}",
+                change.address.yellow(),
+                change.value,
+                "by".dimmed(),
+                change.pc
+            );
+        }
+        false
+    }
+
+    fn asm(&mut self, l: &str) -> bool {
+        let code = l.split_once(' ').map(|(_, code)| code).unwrap_or_default();
+        let snippet = match Program::parse(
+            BufReader::new(code.as_bytes()),
+            true,
+            Lints::default(),
+            None,
+        ) {
+            Ok(snippet) => snippet,
+            Err(e) => {
+                println!("Invalid code: {e}");
+                return false;
+            }
+        };
+        if snippet.tokens.len() == 1 {
+            println!("No code to append");
+            return false;
+        }
+
+        let start = self.interpreter.program().tokens.len() - 1;
+        self.interpreter.append_program(&snippet);
+        let end = self.interpreter.program().tokens.len() - 1;
+        println!("Appended code at {start:#x}..{end:#x}");
+        if !self.running && self.interpreter.pc() == start {
+            self.running = true;
+            println!("Program resumed");
+        }
+        false
+    }
+
+    fn dump_c_array(&self, l: &str) -> bool {
+        let mut args = l.split_whitespace().skip(1);
+        let (Some(start), Some(count), Some(name), None) =
+            (args.next(), args.next(), args.next(), args.next())
+        else {
+            println!("Expected `dump-c-array <address> <count> <name>`");
+            return false;
+        };
+        let (Ok(start), Ok(count)) = (
+            usize::from_str_radix(start.trim_start_matches("0x"), 16),
+            count.parse::<usize>(),
+        ) else {
+            println!("Invalid address or count");
+            return false;
+        };
+        let Some(cells) = start
+            .checked_add(count)
+            .and_then(|end| self.interpreter.tape().get(start..end))
+        else {
+            println!("Region is outside of the tape");
+            return false;
+        };
+
+        let (c_type, width) = match self.interpreter.cell_width() {
+            CellWidth::Bits8 => ("unsigned char", 4),
+            CellWidth::Bits16 => ("uint16_t", 6),
+            CellWidth::Bits32 => ("uint32_t", 10),
+        };
+        println!("{c_type} {name}[{count}] = {{");
+        for line in cells.chunks(12) {
+            let line: Vec<_> = line
+                .iter()
+                .map(|cell| format!("{cell:#0width$x}"))
+                .collect();
+            println!("    {},", line.join(", "));
+        }
+        println!("}};");
+        false
+    }
+
+    fn eval(&self, l: &str) -> bool {
+        /// Steps after which the code is assumed to loop forever
+        const MAX_STEPS: u64 = 10_000_000;
+
+        let code = l.split_once(' ').map(|(_, code)| code).unwrap_or_default();
+        let snippet = match Program::parse(
+            BufReader::new(code.as_bytes()),
+            false,
+            Lints::default(),
+            None,
+        ) {
+            Ok(snippet) => snippet,
+            Err(e) => {
+                println!("Invalid code: {e}");
+                return false;
+            }
+        };
+
+        let mut sandbox = self.interpreter.sandbox(snippet);
+        let result = loop {
+            match sandbox.step() {
+                Ok(true) if sandbox.steps() >= MAX_STEPS => {
+                    break Err(format!("gave up after {MAX_STEPS} steps"));
+                }
+                Ok(true) => (),
+                Ok(false) => break Ok(()),
+                Err(e) => break Err(e.to_string()),
+            }
+        };
+
+        if let Err(e) = result {
+            println!("{}: {e}", "Error".red());
+        }
+        let ptr = sandbox.ptr();
+        println!(
+            "{}: {:#x} = {:#04x} ({})",
+            "Cell".yellow(),
+            ptr,
+            sandbox.tape()[ptr],
+            sandbox.tape()[ptr]
+        );
+        println!("{}: {} steps", "Ran".yellow(), sandbox.steps());
+        if !sandbox.output().is_empty() {
+            println!(
+                "{}: \"{}\"",
+                "Output".yellow(),
+                sandbox.output().escape_ascii()
+            );
+        }
+        false
+    }
+
+    fn preview_cont(&self) -> bool {
+        /// Steps after which the program is assumed to never stop
+        const MAX_STEPS: u64 = 10_000_000;
+
+        let mut fork = self.interpreter.fork();
+        let outcome = match fork.cont_for(MAX_STEPS) {
+            Ok(Some(true)) => {
+                if let Some(breakpoint) = fork.take_data_breakpoint_hit() {
+                    format!("at data breakpoint {breakpoint}")
+                } else if let Some((watch, _)) = fork.take_watch_hit() {
+                    format!("when watch {} changes", watch.name)
+                } else if let Some(change) = fork.take_protection_hit() {
+                    format!("at a write to protected cell {:#x}", change.address)
+                } else {
+                    "at a breakpoint".to_string()
+                }
+            }
+            Ok(Some(false)) => "when the program halts".to_string(),
+            Ok(None) => {
+                println!("No stop within {MAX_STEPS} steps");
+                return false;
+            }
+            Err(InterpreterError::InputWouldBlock) => {
+                "waiting for input, which can't be previewed".to_string()
+            }
+            Err(e) => format!("with an error: {e}"),
+        };
+        println!(
+            "Would stop {outcome}, at {} after {} steps",
+            fork.format_address(fork.pc()),
+            fork.steps() - self.interpreter.steps()
+        );
+        false
+    }
+
+    fn save_snapshot(&self, l: &str) -> bool {
+        // The path is taken from the line as typed, since paths are case sensitive
+        let Some((_, path)) = l.split_once(char::is_whitespace) else {
+            println!("Expected `save-snapshot <file>`");
+            return false;
+        };
+        let path = path.trim();
+        match std::fs::write(path, self.interpreter.snapshot().to_string()) {
+            Ok(()) => println!("Saved snapshot to {path}"),
+            Err(e) => println!("Error saving snapshot: {e}"),
+        }
+        false
+    }
+
+    fn repro(&self, l: &str) -> bool {
+        // The path is taken from the line as typed, since paths are case sensitive
+        let Some((_, path)) = l.split_once(char::is_whitespace) else {
+            println!("Expected `repro <file>`");
+            return false;
+        };
+        let path = path.trim();
+        let repro = Repro {
+            args: self.invocation.clone(),
+            input: self
+                .interpreter
+                .consumed_input()
+                .unwrap_or_default()
+                .to_vec(),
+            commands: self.history.clone(),
+            source: self.source.clone().unwrap_or_else(|| {
+                self.interpreter
+                    .program()
+                    .to_formatted_source()
+                    .into_bytes()
+            }),
+        };
+        match std::fs::write(path, repro.to_bytes()) {
+            Ok(()) => println!(
+                "Saved a reproduction of {} commands to {path}",
+                self.history.len()
+            ),
+            Err(e) => println!("Error saving reproduction: {e}"),
+        }
+        false
+    }
+
+    fn summary(&self) -> bool {
+        self.interpreter.print_tape_summary();
+        false
+    }
+
+    fn stats_tape(&self) -> bool {
+        self.interpreter.print_tape_stats();
+        false
+    }
+
+    fn hash(&self) -> bool {
+        println!("Tape hash: {:016x}", self.interpreter.tape_hash());
+        false
+    }
+
+    fn stack_view(&self, l: &str) -> bool {
+        let depth = match l.split_whitespace().nth(1) {
+            None => 8,
+            Some(depth) if let Ok(depth) = depth.parse() => depth,
+            _ => {
+                println!("Invalid depth");
+                return false;
+            }
+        };
+        self.interpreter.print_stack(depth);
+        false
+    }
+
     fn program(&self) -> bool {
-        println!("{}", self.interpreter.dump_program().0);
+        page(&self.interpreter.dump_program().0);
+        false
+    }
+
+    fn tree(&self, l: &str) -> bool {
+        let program = self.interpreter.program();
+        let range = match l.split_whitespace().nth(1) {
+            None => 0..program.tokens.len(),
+            Some("unit") => {
+                let unit = &program.units[self.interpreter.current_unit()];
+                unit.start..unit.end
+            }
+            Some(_) => {
+                println!("Expected `tree` or `tree unit`");
+                return false;
+            }
+        };
+        page(&self.interpreter.program_tree(range));
+        false
+    }
+
+    fn units(&self) -> bool {
+        let breakpoints = self.interpreter.breakpoints();
+        for (index, unit) in self.interpreter.program().units.iter().enumerate() {
+            let count = breakpoints
+                .iter()
+                .filter(|(address, _)| (unit.start..unit.end).contains(address))
+                .count();
+            let marker = if index == self.interpreter.current_unit() {
+                ">"
+            } else {
+                " "
+            };
+            print!(
+                "{marker} {index}: {:#x}..{:#x} {}",
+                unit.start,
+                unit.end,
+                unit.description.yellow()
+            );
+            if count > 0 {
+                print!(" {}", format!("({count} breakpoints)").red());
+            }
+            println!();
+        }
         false
     }
 
     fn tape(&self) -> bool {
-        self.interpreter.print_tape();
+        let labels: Vec<_> = self
+            .bookmarks
+            .iter()
+            .map(|(name, &address)| (address, name.as_str()))
+            .collect();
+        self.interpreter.print_tape_labeled(&labels);
         false
     }
 
-    fn n(&mut self) -> bool {
-        if !self.running {
-            println!("Program is halted");
+    fn bookmark(&mut self, l: &str) -> bool {
+        let mut args = l.split_whitespace().skip(1);
+        match (args.next(), args.next(), args.next()) {
+            (Some(name), Some(address), None)
+                if let Ok(address) =
+                    usize::from_str_radix(address.trim_start_matches("0x"), 16) =>
+            {
+                if address >= self.interpreter.tape().len() {
+                    println!("Address {address:#x} is outside of the tape");
+                    return false;
+                }
+                let name = name.trim_start_matches('@');
+                self.bookmarks.insert(name.to_string(), address);
+                println!("Bookmarked {address:#x} as {name}");
+            }
+            _ => println!("Expected `bookmark <name> <address>`"),
+        }
+        false
+    }
+
+    fn list_bookmarks(&self) -> bool {
+        if self.bookmarks.is_empty() {
+            println!("No bookmarks");
+        }
+        for (name, address) in &self.bookmarks {
+            println!("  {name}: {address:#x}");
+        }
+        false
+    }
+
+    fn checkpoint(&mut self, l: &str) -> bool {
+        let mut args = l.split_whitespace().skip(1);
+        let (Some(name), None) = (args.next(), args.next()) else {
+            println!("Expected `checkpoint <name>`");
+            return false;
+        };
+        self.checkpoints.insert(
+            name.to_string(),
+            (self.interpreter.checkpoint(), self.running),
+        );
+        println!(
+            "Saved checkpoint {name} at step {}",
+            self.interpreter.steps()
+        );
+        false
+    }
+
+    fn restore(&mut self, l: &str) -> bool {
+        let Some(name) = l.split_whitespace().nth(1) else {
+            println!("Expected `restore <name>`");
+            return false;
+        };
+        let Some((checkpoint, running)) = self.checkpoints.get(name) else {
+            println!("No checkpoint named {name}");
             return false;
+        };
+        self.interpreter.restore(checkpoint);
+        self.running = *running;
+        println!("Restored checkpoint {name}");
+        true
+    }
+
+    fn list_checkpoints(&self) -> bool {
+        if self.checkpoints.is_empty() {
+            println!("No checkpoints");
         }
-        match self.interpreter.step_unit() {
-            Ok(true) => (),
-            Ok(false) => {
-                self.running = false;
-                println!("Program has halted");
+        for (name, (checkpoint, _)) in &self.checkpoints {
+            println!(
+                "  {name}: step {}, PC {}",
+                checkpoint.steps(),
+                self.interpreter.format_address(checkpoint.pc())
+            );
+        }
+        false
+    }
+
+    fn examine(&self, l: &str) -> bool {
+        let address = match l.split_whitespace().nth(1) {
+            Some(name) if let Some(name) = name.strip_prefix('@') => {
+                match self.bookmarks.get(name) {
+                    Some(&address) => address,
+                    None => {
+                        println!("No bookmark named {name}");
+                        return false;
+                    }
+                }
             }
-            Err(e) => {
-                self.running = false;
-                println!("Program has halted with an error:");
-                println!("{e}");
+            Some(address)
+                if let Ok(address) =
+                    usize::from_str_radix(address.trim_start_matches("0x"), 16) =>
+            {
+                address
+            }
+            _ => {
+                println!("Expected a tape address (hex) or a bookmark (@name)");
+                return false;
             }
+        };
+        let Some(&value) = self.interpreter.tape().get(address) else {
+            println!("Address {address:#x} is outside of the tape");
+            return false;
+        };
+
+        print!(
+            "{}: {value:#04x} ({value})",
+            format!("{address:#x}").yellow()
+        );
+        print!(" '{}'", self.interpreter.display_char(value));
+        let names: Vec<_> = self
+            .bookmarks
+            .iter()
+            .filter(|&(_, &bookmark)| bookmark == address)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !names.is_empty() {
+            print!(" {}", names.join(", ").cyan());
         }
-        true
+        println!();
+        if let Some((layout, value)) = self.interpreter.fixed_point_at(address) {
+            println!(
+                "{}: {value}",
+                format!("{address:#x}..{:#x} as {layout}", address + layout.cells()).yellow()
+            );
+        }
+        false
     }
 
-    fn ni(&mut self) -> bool {
+    /// Runs a motion command on the interpreter, unless the program has already halted, and
+    /// reports whether it halted during the motion
+    fn motion(
+        &mut self,
+        motion: impl FnOnce(&mut Interpreter<T, W>) -> Result<bool, InterpreterError>,
+    ) -> bool {
         if !self.running {
             println!("Program is halted");
             return false;
         }
-        match self.interpreter.step() {
+        let steps = self.interpreter.steps();
+        let result = motion(&mut self.interpreter);
+        println!("(executed {} steps)", self.interpreter.steps() - steps);
+        let step = self.interpreter.steps();
+        let pc = self.interpreter.format_address(self.interpreter.pc());
+        if let Some(breakpoint) = self.interpreter.take_data_breakpoint_hit() {
+            println!("Data breakpoint {breakpoint} triggered at step {step}, before {pc}");
+        }
+        if let Some((watch, old)) = self.interpreter.take_watch_hit() {
+            println!(
+                "Watch {} changed from {old} to {} at step {step}, before {pc}",
+                watch.name, watch.value
+            );
+        }
+        if let Some(write) = self.interpreter.take_protection_hit() {
+            println!(
+                "Protected cell {:#x} was set to {:#04x} by {}",
+                write.address,
+                write.value,
+                self.interpreter.format_address(write.pc)
+            );
+        }
+        match result {
             Ok(true) => (),
             Ok(false) => {
                 self.running = false;
                 println!("Program has halted");
             }
+            Err(InterpreterError::OutputLimitExceeded(max_output)) => {
+                self.running = false;
+                println!(
+                    "Program has halted after reaching the output limit of {max_output} bytes"
+                );
+            }
             Err(e) => {
                 self.running = false;
                 println!("Program has halted with an error:");
                 println!("{e}");
             }
         }
+        if !self.running && self.halt_summary {
+            self.print_halt_summary();
+        }
         true
     }
 
+    fn print_halt_summary(&self) {
+        println!("{}", "Halt summary:".blue().bold());
+        println!("{}: {}", "Steps".yellow(), self.interpreter.steps());
+        println!("{}: {:#x}", "PC".yellow(), self.interpreter.pc());
+        println!("{}: {:#x}", "TP".yellow(), self.interpreter.ptr());
+        println!("{}: {:#x}", "Max TP".yellow(), self.interpreter.max_ptr());
+        println!(
+            "{}: {}",
+            "Input bytes".yellow(),
+            self.interpreter.input_bytes()
+        );
+        println!(
+            "{}: {}",
+            "Output bytes".yellow(),
+            self.interpreter.output_bytes()
+        );
+        println!("{}", "Tape summary:".blue().bold());
+        self.interpreter.print_tape_summary();
+    }
+
+    fn set_halt_summary(&mut self, l: &str) -> bool {
+        match l.split_whitespace().nth(1) {
+            Some("on") => self.halt_summary = true,
+            Some("off") => self.halt_summary = false,
+            _ => {
+                println!("Expected on or off");
+                return false;
+            }
+        }
+        println!(
+            "Halt summary {}",
+            if self.halt_summary { "on" } else { "off" }
+        );
+        false
+    }
+
+    fn set_ctx_on_unit_change(&mut self, l: &str) -> bool {
+        match l.split_whitespace().nth(1) {
+            Some("on") => self.ctx_on_unit_change = true,
+            Some("off") => self.ctx_on_unit_change = false,
+            _ => {
+                println!("Expected on or off");
+                return false;
+            }
+        }
+        println!(
+            "Context on unit change {}",
+            if self.ctx_on_unit_change { "on" } else { "off" }
+        );
+        false
+    }
+
+    fn set_jump_annotations(&mut self, l: &str) -> bool {
+        match l.split_whitespace().nth(1) {
+            Some("on") => self.interpreter.set_jump_annotations(true),
+            Some("off") => self.interpreter.set_jump_annotations(false),
+            _ => {
+                println!("Expected on or off");
+                return false;
+            }
+        }
+        println!(
+            "Jump annotations {}",
+            if self.interpreter.jump_annotations() {
+                "on"
+            } else {
+                "off"
+            }
+        );
+        false
+    }
+
+    fn n(&mut self) -> bool {
+        self.motion(Interpreter::step_unit)
+    }
+
+    fn ni(&mut self) -> bool {
+        self.motion(Interpreter::step)
+    }
+
     fn breakpoint(&mut self, l: &str) -> bool {
-        if let Some(s) = l.split_whitespace().nth(1)
-            && let Ok(v) = usize::from_str_radix(s.trim_start_matches("0x"), 16)
-        {
-            println!("Added breakpoint at {v:#x}");
-            self.interpreter.add_breakpoint(v);
-        } else {
+        let mut args = l.split_whitespace().skip(1);
+        let Some(v) = args.next().and_then(|s| self.parse_location(s)) else {
             println!("Invalid breakpoint");
+            return false;
+        };
+
+        let ignore_count = match (args.next(), args.next()) {
+            (None, _) => 0,
+            (Some("ignore"), Some(count)) if let Ok(count) = count.parse() => count,
+            _ => {
+                println!("Expected `break <location> [ignore <count>]`");
+                return false;
+            }
+        };
+
+        self.interpreter.add_breakpoint(v);
+        self.interpreter
+            .set_breakpoint_ignore_count(v, ignore_count);
+        if ignore_count > 0 {
+            println!(
+                "Added breakpoint at {}, ignoring its next {ignore_count} hits",
+                self.interpreter.format_address(v)
+            );
+        } else {
+            println!("Added breakpoint at {}", self.interpreter.format_address(v));
         }
         false
     }
 
+    fn status(&self) -> bool {
+        let tape = self.interpreter.tape();
+        let breakpoints = self.interpreter.data_breakpoints();
+        let watches = self.interpreter.watch_expressions();
+        if breakpoints.is_empty() && watches.is_empty() {
+            println!("No data breakpoints or watch expressions");
+        }
+        for breakpoint in breakpoints {
+            let cell = tape[breakpoint.address];
+            print!("  cell {:#x} is {cell:#04x}, ", breakpoint.address);
+            let max = self.interpreter.cell_width().max();
+            match breakpoint.comparison.distance(cell, breakpoint.value, max) {
+                Some(0) => println!(
+                    "which is {} {:#04x}, so it only triggers once that stops and starts holding again",
+                    breakpoint.comparison, breakpoint.value
+                ),
+                Some(distance) => println!(
+                    "waiting for {} {:#04x} ({distance} away)",
+                    breakpoint.comparison, breakpoint.value
+                ),
+                None => println!(
+                    "waiting for {} {:#04x}, which can never hold",
+                    breakpoint.comparison, breakpoint.value
+                ),
+            }
+        }
+        for watch in watches {
+            println!("  watch {watch}, waiting for any change");
+        }
+        false
+    }
+
+    fn break_when(&mut self, l: &str) -> bool {
+        let mut args = l.split_whitespace().skip(1);
+        let parse_hex = |s: &str| usize::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (None, ..) => {
+                if self.interpreter.data_breakpoints().is_empty() {
+                    println!("No data breakpoints");
+                }
+                for breakpoint in self.interpreter.data_breakpoints() {
+                    println!("  break when {breakpoint}");
+                }
+            }
+            (Some("clear"), None, ..) => {
+                self.interpreter.clear_data_breakpoints();
+                println!("Cleared every data breakpoint");
+            }
+            (Some(address), Some(comparison), Some(value), None) => {
+                let comparison = match comparison {
+                    "==" => Comparison::Equal,
+                    "!=" => Comparison::NotEqual,
+                    "<" => Comparison::Less,
+                    ">" => Comparison::Greater,
+                    _ => {
+                        println!("Expected one of ==, !=, < or >");
+                        return false;
+                    }
+                };
+                let max = self.interpreter.cell_width().max();
+                let (Some(address), Some(value)) = (
+                    parse_hex(address),
+                    parse_hex(value)
+                        .and_then(|value| u32::try_from(value).ok())
+                        .filter(|&value| value <= max),
+                ) else {
+                    println!("Invalid address or value");
+                    return false;
+                };
+                if address >= self.interpreter.tape().len() {
+                    println!("Address {address:#x} is outside of the tape");
+                    return false;
+                }
+                self.interpreter
+                    .add_data_breakpoint(address, comparison, value);
+                println!("Added data breakpoint {address:#x} {comparison} {value:#04x}");
+            }
+            _ => println!("Expected `break-when <address> <comparison> <value>`"),
+        }
+        false
+    }
+
+    fn watch_expr(&mut self, l: &str) -> bool {
+        let mut args = l.split_whitespace().skip(1);
+        match (args.next(), args.next(), args.next()) {
+            (None, ..) => {
+                if self.interpreter.watch_expressions().is_empty() {
+                    println!("No watch expressions");
+                }
+                for watch in self.interpreter.watch_expressions() {
+                    println!("  {watch}");
+                }
+            }
+            (Some("clear"), None, _) => {
+                self.interpreter.clear_watch_expressions();
+                println!("Cleared every watch expression");
+            }
+            (Some(name), Some(region), None) => {
+                let region = region.split_once(':').and_then(|(address, len)| {
+                    Some((
+                        usize::from_str_radix(address.trim_start_matches("0x"), 16).ok()?,
+                        len.parse::<usize>().ok()?,
+                    ))
+                });
+                let Some((address, len)) = region else {
+                    println!("Expected a region such as 0x10:4");
+                    return false;
+                };
+                let max_len = 64 / self.interpreter.cell_width().bits() as usize;
+                if !(1..=max_len).contains(&len) {
+                    println!("The region must be 1 to {max_len} cells long");
+                    return false;
+                }
+                if address
+                    .checked_add(len)
+                    .is_none_or(|end| end > self.interpreter.tape().len())
+                {
+                    println!("Region {address:#x}:{len} is outside of the tape");
+                    return false;
+                }
+                self.interpreter
+                    .add_watch_expression(name.to_string(), address, len);
+                let watch = self.interpreter.watch_expressions().last().unwrap();
+                println!("Watching {watch}");
+            }
+            _ => println!("Expected `watch-expr <name> <address>:<length>`"),
+        }
+        false
+    }
+
+    fn freeze(&mut self) -> bool {
+        let ranges = self.interpreter.freeze();
+        let cells: usize = ranges.iter().map(|range| range.len()).sum();
+        println!("Protected {cells} cells in {} ranges", ranges.len());
+        for range in ranges {
+            println!("  {:#x}..{:#x}", range.start, range.end);
+        }
+        false
+    }
+
+    fn thaw(&mut self) -> bool {
+        self.interpreter.unprotect_all();
+        println!("Removed every protection");
+        false
+    }
+
     fn clear(&mut self, l: &str) -> bool {
-        if let Some(s) = l.split_whitespace().nth(1)
-            && let Ok(v) = usize::from_str_radix(s.trim_start_matches("0x"), 16)
+        if let Some(v) = l
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| self.parse_location(s))
         {
             if self.interpreter.clear_breakpoint(v) {
-                println!("Cleared breakpoint at {v:#x}");
+                println!(
+                    "Cleared breakpoint at {}",
+                    self.interpreter.format_address(v)
+                );
             } else {
-                println!("No breakpoint at {v:#x}");
+                println!("No breakpoint at {}", self.interpreter.format_address(v));
             }
         } else {
             println!("Invalid breakpoint");
@@ -161,23 +1240,295 @@ impl<T: Read> Debugger<T> {
         false
     }
 
+    fn break_all_units(&mut self) -> bool {
+        let units = self.interpreter.break_all_units();
+        println!("Added breakpoints at the start of {units} units");
+        false
+    }
+
+    fn clear_all_units(&mut self) -> bool {
+        let cleared = self.interpreter.clear_all_units();
+        println!("Cleared {cleared} breakpoints at the start of units");
+        false
+    }
+
     fn cont(&mut self) -> bool {
-        if !self.running {
-            println!("Program is halted");
+        self.motion(Interpreter::cont)
+    }
+
+    fn watch_run(&mut self, l: &str) -> bool {
+        let delay = match l.split_whitespace().nth(1) {
+            None => 100,
+            Some(delay) if let Ok(delay) = delay.parse() => delay,
+            _ => {
+                println!("Invalid delay");
+                return false;
+            }
+        };
+        let delay = Duration::from_millis(delay);
+        let bookmarks: Vec<_> = self
+            .bookmarks
+            .iter()
+            .map(|(name, &address)| (address, name.clone()))
+            .collect();
+        let interrupt = self.interrupt.clone();
+        interrupt.store(false, Ordering::Relaxed);
+        // Without a terminal, key presses can't be read as they happen, and when the program reads
+        // stdin, they are its input. Either way, only Ctrl-C stops it.
+        let keys = if self.program_reads_stdin {
+            None
+        } else {
+            KeyPoller::new()
+        };
+        let stop_hint = if keys.is_some() {
+            "press any key to stop"
+        } else {
+            "Ctrl-C to stop"
+        };
+
+        self.motion(|interpreter| {
+            let mut lines = 0;
+            loop {
+                // Move back up over the last frame and clear it, so the tape is redrawn in place
+                if lines > 0 {
+                    print!("\x1b[{lines}A\x1b[J");
+                }
+                let mut labels: Vec<_> = bookmarks
+                    .iter()
+                    .map(|(address, name)| (*address, name.as_str()))
+                    .collect();
+                if let Some(change) = interpreter.change_history().back() {
+                    labels.push((change.address, "last write"));
+                }
+                lines = interpreter.print_tape_labeled(&labels) + 1;
+                println!(
+                    "Step {}, before {} ({stop_hint})",
+                    interpreter.steps(),
+                    interpreter.format_address(interpreter.pc())
+                );
+                io::stdout().flush().unwrap(); // TODO handle this unwrap
+
+                let key_pressed = keys.as_ref().is_some_and(KeyPoller::key_pressed);
+                if interrupt.swap(false, Ordering::Relaxed) || key_pressed {
+                    return Ok(true);
+                }
+                std::thread::sleep(delay);
+                if let Some(running) = interpreter.cont_for(1)? {
+                    return Ok(running);
+                }
+            }
+        })
+    }
+
+    fn finish(&mut self) -> bool {
+        let Some(&start) = self.interpreter.loop_stack().last() else {
+            println!("Not inside a loop");
             return false;
+        };
+        let depth = self.interpreter.loop_stack().len();
+        let result = self.motion(Interpreter::finish);
+        if self.running {
+            if self.interpreter.loop_stack().len() < depth {
+                println!("Exited loop at {}", self.interpreter.format_address(start));
+            } else {
+                println!(
+                    "Hit a breakpoint before exiting loop at {}",
+                    self.interpreter.format_address(start)
+                );
+            }
         }
-        match self.interpreter.cont() {
-            Ok(true) => (),
-            Ok(false) => {
-                self.running = false;
-                println!("Program has halted");
+        result
+    }
+
+    fn next_output(&mut self) -> bool {
+        let output_bytes = self.interpreter.output_bytes();
+        let was_running = self.running;
+        let result = self.motion(Interpreter::run_to_output);
+        if self.interpreter.output_bytes() > output_bytes {
+            let ptr = self.interpreter.ptr();
+            let cell = self.interpreter.tape()[ptr];
+            println!(
+                "Output {cell:#04x} '{}' from cell {ptr:#x}, at {}",
+                [cell as u8].escape_ascii(),
+                self.interpreter.format_address(self.interpreter.pc() - 1)
+            );
+        } else if self.running {
+            println!("Hit a breakpoint before any output");
+        } else if was_running {
+            println!("Halted before any output");
+        }
+        result
+    }
+
+    fn run_to_unit(&mut self, l: &str) -> bool {
+        let name = l
+            .split_once(' ')
+            .map(|(_, name)| name.trim())
+            .unwrap_or_default();
+        if name.is_empty() {
+            println!("Missing unit name");
+            return false;
+        }
+        let Some(unit) = self.interpreter.program().find_unit(name) else {
+            println!("No unit named {name}");
+            return false;
+        };
+        self.motion(|interpreter| interpreter.run_to_unit(unit))
+    }
+
+    fn list_breakpoints(&self) -> bool {
+        let breakpoints = self.interpreter.breakpoints();
+        if breakpoints.is_empty() {
+            println!("No breakpoints");
+        }
+        for (index, (address, breakpoint)) in breakpoints.iter().enumerate() {
+            let address = self.interpreter.format_address(*address);
+            let status = if breakpoint.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            if breakpoint.ignore_count > 0 {
+                println!(
+                    "  {index}: breakpoint at {address} ({status}, ignoring next {} hits)",
+                    breakpoint.ignore_count
+                );
+            } else {
+                println!("  {index}: breakpoint at {address} ({status})");
             }
-            Err(e) => {
-                self.running = false;
-                println!("Program has halted with an error:");
-                println!("{e}");
+        }
+        false
+    }
+
+    /// Looks up the address of the breakpoint whose index (as shown by `breakpoints`) is the
+    /// command's argument
+    fn breakpoint_by_index(&self, l: &str) -> Option<usize> {
+        let index: usize = l.split_whitespace().nth(1)?.parse().ok()?;
+        self.interpreter
+            .breakpoints()
+            .get(index)
+            .map(|(address, _)| *address)
+    }
+
+    fn delete(&mut self, l: &str) -> bool {
+        match self.breakpoint_by_index(l) {
+            Some(address) => {
+                self.interpreter.clear_breakpoint(address);
+                println!(
+                    "Deleted breakpoint at {}",
+                    self.interpreter.format_address(address)
+                );
             }
+            None => println!("Invalid breakpoint index"),
         }
-        true
+        false
+    }
+
+    fn set_enabled(&mut self, l: &str, enabled: bool) -> bool {
+        match self.breakpoint_by_index(l) {
+            Some(address) => {
+                self.interpreter.set_breakpoint_enabled(address, enabled);
+                let status = if enabled { "Enabled" } else { "Disabled" };
+                println!(
+                    "{status} breakpoint at {}",
+                    self.interpreter.format_address(address)
+                );
+            }
+            None => println!("Invalid breakpoint index"),
+        }
+        false
     }
 }
+
+/// Prints text through a pager (`$PAGER`, or `less -R` by default) when stdout is a terminal and
+/// the text doesn't fit in it. Falls back to printing it directly if no pager can be run.
+fn page(text: &str) {
+    if io::stdout().is_terminal()
+        && text.lines().count() >= terminal_dimension("LINES", "lines", 24)
+    {
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+        let mut parts = pager.split_whitespace();
+        if let Some(program) = parts.next()
+            && let Ok(mut child) = Command::new(program)
+                .args(parts)
+                .stdin(Stdio::piped())
+                .spawn()
+        {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may quit before reading everything, which isn't an error
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            if child.wait().is_ok() {
+                return;
+            }
+        }
+    }
+    println!("{text}");
+}
+
+/// Switches the terminal to non-canonical mode without echo while alive, so key presses can be read
+/// from stdin as they happen without blocking. The previous settings are restored on drop.
+struct KeyPoller {
+    saved: String,
+}
+
+impl KeyPoller {
+    /// Returns `None` if stdin isn't a terminal, or its settings can't be changed, such as when
+    /// `stty` isn't available. The terminal is left as it was in that case.
+    fn new() -> Option<KeyPoller> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let saved = stty(&["-g"])?.trim().to_string();
+        if saved.is_empty() {
+            return None;
+        }
+        let poller = KeyPoller { saved };
+        // Dropping the poller restores the saved settings, in case some were changed
+        stty(&["-icanon", "-echo", "min", "0", "time", "0"])?;
+        Some(poller)
+    }
+
+    /// Returns whether any key was pressed since the last call, consuming the pending keys
+    fn key_pressed(&self) -> bool {
+        let mut buffer = [0; 64];
+        matches!(io::stdin().read(&mut buffer), Ok(read) if read > 0)
+    }
+}
+
+impl Drop for KeyPoller {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+/// Runs `stty` on the terminal attached to stdin, returning its output if it succeeded
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns a dimension of the terminal from an environment variable such as `$LINES`, or else from
+/// the `tput` capability such as `lines`, or `default` if it's unknown
+pub(crate) fn terminal_dimension(variable: &str, capability: &str, default: usize) -> usize {
+    std::env::var(variable)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .or_else(|| {
+            let output = Command::new("tput")
+                .arg(capability)
+                .stderr(Stdio::null())
+                .output()
+                .ok()?;
+            String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+        })
+        .unwrap_or(default)
+}
@@ -0,0 +1,375 @@
+use crate::interpreter::options::{RunOptions, RunOptionsError};
+use crate::interpreter::{CellWidth, Endianness, EofBehaviour, Interpreter, WideOutput};
+use crate::parser::{Lints, ParserError, Program};
+use clap::ValueEnum;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+use thiserror::Error;
+
+/// The files a fixture is saved as, inside its directory
+const SOURCE_FILE: &str = "program.bf";
+const INPUT_FILE: &str = "input";
+const OUTPUT_FILE: &str = "output";
+const MANIFEST_FILE: &str = "fixture";
+
+#[derive(Error, Debug)]
+pub enum FixtureError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Error parsing program: {0}")]
+    Parser(ParserError),
+    #[error("{0}")]
+    Options(#[from] RunOptionsError),
+    #[error("{MANIFEST_FILE} line {0}: expected `<field>: <value>`")]
+    BadLine(usize),
+    #[error("{MANIFEST_FILE} line {0}: unknown field or invalid value")]
+    BadField(usize),
+    #[error("{MANIFEST_FILE}: missing field `{0}`")]
+    MissingField(&'static str),
+}
+
+/// A recording of how a program behaved on some input, which can be saved to a directory and
+/// verified later to catch changes in the interpreter's behaviour.
+///
+/// A fixture's directory holds the program's source, its input and output, and a manifest with
+/// one `<field>: <value>` line for the interpreter's options and the recorded results. Run options
+/// left at their defaults are left out of the manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    pub source: Vec<u8>,
+    pub input: Vec<u8>,
+    pub tape_size: usize,
+    pub fill_byte: u8,
+    pub eof_behaviour: EofBehaviour,
+    pub options: RunOptions,
+    pub output: Vec<u8>,
+    pub steps: u64,
+    pub tape_hash: u64,
+    /// The error the program stopped with, if it didn't halt normally
+    pub error: Option<String>,
+}
+
+impl Fixture {
+    /// Runs a program's source on the given input, recording its behaviour
+    pub fn record(
+        source: Vec<u8>,
+        input: Vec<u8>,
+        tape_size: usize,
+        fill_byte: u8,
+        eof_behaviour: EofBehaviour,
+        options: RunOptions,
+    ) -> Result<Fixture, FixtureError> {
+        let mut fixture = Fixture {
+            source,
+            input,
+            tape_size,
+            fill_byte,
+            eof_behaviour,
+            options,
+            output: Vec::new(),
+            steps: 0,
+            tape_hash: 0,
+            error: None,
+        };
+        let interpreter = fixture.run()?;
+        fixture.output = interpreter.output().clone();
+        fixture.steps = interpreter.steps();
+        fixture.tape_hash = interpreter.tape_hash();
+        Ok(fixture)
+    }
+
+    /// Runs the program again, and lists every recorded value it no longer matches
+    pub fn verify(&self) -> Result<Vec<String>, FixtureError> {
+        let mut rerun = self.clone();
+        let interpreter = rerun.run()?;
+
+        let mut mismatches = Vec::new();
+        if rerun.error != self.error {
+            mismatches.push(format!(
+                "expected the run to end with {}, but it ended with {}",
+                describe_error(&self.error),
+                describe_error(&rerun.error)
+            ));
+        }
+        if *interpreter.output() != self.output {
+            mismatches.push(format!(
+                "expected output \"{}\", but got \"{}\"",
+                self.output.escape_ascii(),
+                interpreter.output().escape_ascii()
+            ));
+        }
+        if interpreter.steps() != self.steps {
+            mismatches.push(format!(
+                "expected {} steps, but ran {}",
+                self.steps,
+                interpreter.steps()
+            ));
+        }
+        if interpreter.tape_hash() != self.tape_hash {
+            mismatches.push(format!(
+                "expected tape hash {:016x}, but got {:016x}",
+                self.tape_hash,
+                interpreter.tape_hash()
+            ));
+        }
+        Ok(mismatches)
+    }
+
+    /// Runs the program to completion, setting `error` to how it failed, if it did
+    fn run(&mut self) -> Result<Interpreter<Cursor<Vec<u8>>, Vec<u8>>, FixtureError> {
+        let program = Program::parse(
+            BufReader::new(self.source.as_slice()),
+            false,
+            Lints::default(),
+            None,
+        )
+        .map_err(FixtureError::Parser)?;
+        let mut interpreter = Interpreter::new(
+            program,
+            self.tape_size,
+            self.fill_byte,
+            self.eof_behaviour,
+            Cursor::new(self.input.clone()),
+            Vec::new(),
+        );
+        self.options.apply(&mut interpreter)?;
+        self.error = interpreter.run().err().map(|e| e.to_string());
+        Ok(interpreter)
+    }
+
+    /// Saves the fixture to a directory, creating it if needed
+    pub fn save(&self, dir: &Path) -> Result<(), FixtureError> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(SOURCE_FILE), &self.source)?;
+        std::fs::write(dir.join(INPUT_FILE), &self.input)?;
+        std::fs::write(dir.join(OUTPUT_FILE), &self.output)?;
+
+        let mut manifest = format!(
+            "tape-size: {}\nfill-byte: {:#04x}\neof: {}\nsteps: {}\ntape-hash: {:016x}\n",
+            self.tape_size, self.fill_byte, self.eof_behaviour, self.steps, self.tape_hash
+        );
+        let options = &self.options;
+        if let Some(cell_width) = options.cell_width {
+            manifest.push_str(&format!("cell-width: {cell_width}\n"));
+        }
+        if !options.preset_cells.is_empty() {
+            let cells: Vec<_> = options
+                .preset_cells
+                .iter()
+                .map(|(address, value)| format!("{address:x}={value:02x}"))
+                .collect();
+            manifest.push_str(&format!("preset-cells: {}\n", cells.join(",")));
+        }
+        if let Some(range) = &options.cell_range {
+            manifest.push_str(&format!(
+                "cell-range: {:02x}-{:02x}\n",
+                range.start(),
+                range.end()
+            ));
+        }
+        if options.ring_tape {
+            manifest.push_str("ring-tape: true\n");
+        }
+        if let Some(max_output) = options.max_output {
+            manifest.push_str(&format!("max-output: {max_output}\n"));
+        }
+        if let Some(gate) = options.output_gate {
+            manifest.push_str(&format!("output-gate: {gate:#x}\n"));
+        }
+        if options.wide_output != WideOutput::default() {
+            manifest.push_str(&format!(
+                "wide-output: {}\n",
+                value_name(options.wide_output)
+            ));
+        }
+        if options.output_endianness != Endianness::default() {
+            manifest.push_str(&format!(
+                "output-endianness: {}\n",
+                value_name(options.output_endianness)
+            ));
+        }
+        if let Some(error) = &self.error {
+            manifest.push_str(&format!("error: {error}\n"));
+        }
+        std::fs::write(dir.join(MANIFEST_FILE), manifest)?;
+        Ok(())
+    }
+
+    /// Loads a fixture saved with `Fixture::save`
+    pub fn load(dir: &Path) -> Result<Fixture, FixtureError> {
+        let (mut tape_size, mut fill_byte, mut eof_behaviour, mut steps, mut tape_hash) =
+            (None, None, None, None, None);
+        let mut error = None;
+        let mut options = RunOptions::default();
+        let manifest = std::fs::read_to_string(dir.join(MANIFEST_FILE))?;
+        for (line_number, line) in manifest.lines().enumerate() {
+            let line_number = line_number + 1;
+            let (field, value) = line
+                .split_once(": ")
+                .ok_or(FixtureError::BadLine(line_number))?;
+            let parsed = match field {
+                "tape-size" => value.parse().ok().map(|value| tape_size = Some(value)),
+                "fill-byte" => u8::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .ok()
+                    .map(|value| fill_byte = Some(value)),
+                "eof" => parse_eof_behaviour(value).map(|value| eof_behaviour = Some(value)),
+                "steps" => value.parse().ok().map(|value| steps = Some(value)),
+                "tape-hash" => u64::from_str_radix(value, 16)
+                    .ok()
+                    .map(|value| tape_hash = Some(value)),
+                "error" => {
+                    error = Some(value.to_string());
+                    Some(())
+                }
+                "cell-width" => CellWidth::from_str(value, false)
+                    .ok()
+                    .map(|value| options.cell_width = Some(value)),
+                "preset-cells" => value
+                    .split(',')
+                    .map(|cell| {
+                        let (address, value) = cell.split_once('=')?;
+                        Some((parse_hex(address)?, u8::try_from(parse_hex(value)?).ok()?))
+                    })
+                    .collect::<Option<_>>()
+                    .map(|cells| options.preset_cells = cells),
+                "cell-range" => value
+                    .split_once('-')
+                    .and_then(|(low, high)| {
+                        let low = u8::try_from(parse_hex(low)?).ok()?;
+                        let high = u8::try_from(parse_hex(high)?).ok()?;
+                        (low <= high).then_some(low..=high)
+                    })
+                    .map(|range| options.cell_range = Some(range)),
+                "ring-tape" => value.parse().ok().map(|value| options.ring_tape = value),
+                "max-output" => value
+                    .parse()
+                    .ok()
+                    .map(|value| options.max_output = Some(value)),
+                "output-gate" => parse_hex(value).map(|value| options.output_gate = Some(value)),
+                "wide-output" => WideOutput::from_str(value, false)
+                    .ok()
+                    .map(|value| options.wide_output = value),
+                "output-endianness" => Endianness::from_str(value, false)
+                    .ok()
+                    .map(|value| options.output_endianness = value),
+                _ => None,
+            };
+            parsed.ok_or(FixtureError::BadField(line_number))?;
+        }
+
+        Ok(Fixture {
+            source: std::fs::read(dir.join(SOURCE_FILE))?,
+            input: std::fs::read(dir.join(INPUT_FILE))?,
+            tape_size: tape_size.ok_or(FixtureError::MissingField("tape-size"))?,
+            fill_byte: fill_byte.ok_or(FixtureError::MissingField("fill-byte"))?,
+            eof_behaviour: eof_behaviour.ok_or(FixtureError::MissingField("eof"))?,
+            options,
+            output: std::fs::read(dir.join(OUTPUT_FILE))?,
+            steps: steps.ok_or(FixtureError::MissingField("steps"))?,
+            tape_hash: tape_hash.ok_or(FixtureError::MissingField("tape-hash"))?,
+            error,
+        })
+    }
+}
+
+/// Parses an EOF behaviour as displayed, including `set 0x..` for a specific value
+fn parse_eof_behaviour(value: &str) -> Option<EofBehaviour> {
+    match value.strip_prefix("set ") {
+        Some(value) => u8::from_str_radix(value.trim_start_matches("0x"), 16)
+            .ok()
+            .map(EofBehaviour::SetValue),
+        None => EofBehaviour::from_str(value, true).ok(),
+    }
+}
+
+/// Parses a hex number, with or without a `0x` prefix
+fn parse_hex(value: &str) -> Option<usize> {
+    usize::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+/// Returns the name an option's value is given on the command line
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn describe_error(error: &Option<String>) -> String {
+    match error {
+        Some(error) => format!("the error \"{error}\""),
+        None => "a halt".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records a fixture for `source`, saves and loads it back, and checks that it still verifies
+    fn roundtrip(source: &str, options: RunOptions) -> Fixture {
+        let fixture = Fixture::record(
+            source.as_bytes().to_vec(),
+            Vec::new(),
+            16,
+            0,
+            EofBehaviour::DontSet,
+            options,
+        )
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!(
+            "brainstorm-fixture-{}-{}",
+            std::process::id(),
+            fixture.tape_hash
+        ));
+        fixture.save(&dir).unwrap();
+        let loaded = Fixture::load(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, fixture);
+        assert_eq!(loaded.verify().unwrap(), Vec::<String>::new());
+        fixture
+    }
+
+    #[test]
+    fn records_and_verifies_a_ring_tape_program() {
+        let options = RunOptions {
+            ring_tape: true,
+            ..RunOptions::default()
+        };
+        let fixture = roundtrip("<+.", options);
+        assert_eq!(fixture.error, None);
+        assert_eq!(fixture.output, [1]);
+    }
+
+    #[test]
+    fn records_and_verifies_a_16_bit_program() {
+        let options = RunOptions {
+            cell_width: Some(CellWidth::Bits16),
+            preset_cells: vec![(0, 0xff)],
+            wide_output: WideOutput::AllBytes,
+            output_endianness: Endianness::Big,
+            ..RunOptions::default()
+        };
+        let fixture = roundtrip("+.", options);
+        assert_eq!(fixture.output, [0x01, 0x00]);
+    }
+
+    #[test]
+    fn verifying_uses_the_recorded_options() {
+        let mut fixture = Fixture::record(
+            b"<+.".to_vec(),
+            Vec::new(),
+            16,
+            0,
+            EofBehaviour::DontSet,
+            RunOptions {
+                ring_tape: true,
+                ..RunOptions::default()
+            },
+        )
+        .unwrap();
+        fixture.options.ring_tape = false;
+        assert_eq!(fixture.verify().unwrap().len(), 4);
+    }
+}
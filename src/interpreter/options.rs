@@ -0,0 +1,66 @@
+use crate::interpreter::{CellWidth, Endianness, Interpreter, WideOutput};
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RunOptionsError {
+    #[error("Preset cell {0:#x} is outside of the tape")]
+    PresetCellOutsideTape(usize),
+    #[error("Output gate cell {0:#x} is outside of the tape")]
+    OutputGateOutsideTape(usize),
+}
+
+/// The options that change what a program does, rather than how it's shown, so that every run of
+/// a program, whether it's the main run, a fuzzing run or a fixture, has the same semantics.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RunOptions {
+    /// The width of every cell, or `None` to use the program's `;!cell-width` directive
+    pub cell_width: Option<CellWidth>,
+    /// Values written into cells before the program starts, as `(address, value)` pairs
+    pub preset_cells: Vec<(usize, u8)>,
+    pub cell_range: Option<RangeInclusive<u8>>,
+    pub ring_tape: bool,
+    pub max_output: Option<u64>,
+    pub output_gate: Option<usize>,
+    pub wide_output: WideOutput,
+    pub output_endianness: Endianness,
+}
+
+impl RunOptions {
+    /// Applies the options to an interpreter that hasn't started running. Fails if a cell they
+    /// name is outside of the tape, without changing the interpreter.
+    pub fn apply<R: Read, W: Write>(
+        &self,
+        interpreter: &mut Interpreter<R, W>,
+    ) -> Result<(), RunOptionsError> {
+        let tape_size = interpreter.tape().len();
+        if let Some(&(address, _)) = self
+            .preset_cells
+            .iter()
+            .find(|(address, _)| *address >= tape_size)
+        {
+            return Err(RunOptionsError::PresetCellOutsideTape(address));
+        }
+        if let Some(address) = self.output_gate
+            && address >= tape_size
+        {
+            return Err(RunOptionsError::OutputGateOutsideTape(address));
+        }
+
+        let cell_width = self
+            .cell_width
+            .or(interpreter.program().options.cell_width)
+            .unwrap_or_default();
+        interpreter.set_cell_width(cell_width);
+        for &(address, value) in &self.preset_cells {
+            interpreter.write_cells(address, &[value as u32]);
+        }
+        interpreter.set_cell_range(self.cell_range.clone());
+        interpreter.set_ring_tape(self.ring_tape);
+        interpreter.set_max_output(self.max_output);
+        interpreter.set_output_gate(self.output_gate);
+        interpreter.set_wide_output(self.wide_output, self.output_endianness);
+        Ok(())
+    }
+}
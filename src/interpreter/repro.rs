@@ -0,0 +1,120 @@
+use crate::interpreter::transcript::unescape;
+use thiserror::Error;
+
+/// The first line of every reproduction file, holding the format's version
+const HEADER: &str = "brainstorm-repro 2";
+
+#[derive(Error, Debug)]
+pub enum ReproError {
+    #[error("not a reproduction file, or an unsupported version of the format")]
+    BadHeader,
+    #[error("line {0}: expected `arg: `, `input: `, `command: ` or `source:`")]
+    BadLine(usize),
+    #[error("line {0}: invalid escape sequence")]
+    BadEscape(usize),
+    #[error("missing `source:` section")]
+    MissingSource,
+}
+
+/// Everything needed to reproduce a debugger session: the command line it was started with, the
+/// program, the input it had consumed and the commands entered.
+///
+/// The text format is stable. Its first line is `brainstorm-repro 2`, followed by one line per
+/// value in the order of the fields below:
+/// - `arg: <argument>` for every command line argument, including the executable's name
+/// - `input: <bytes>` for the input consumed, if any
+/// - `command: <command>` for every debugger command entered, in order
+///
+/// Arguments, input and commands are escaped like Rust byte strings (`\n`, `\x00`, ...). A
+/// `source:` line ends the header, and everything after it is the program's source, byte for byte
+/// as it was read, in the dialect selected by the arguments.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Repro {
+    pub args: Vec<String>,
+    pub input: Vec<u8>,
+    pub commands: Vec<String>,
+    pub source: Vec<u8>,
+}
+
+impl Repro {
+    /// Parses a reproduction from its text format. Only the source may hold bytes that aren't
+    /// valid UTF-8.
+    pub fn parse(bytes: &[u8]) -> Result<Repro, ReproError> {
+        let mut lines = bytes.split_inclusive(|&byte| byte == b'\n').enumerate();
+        // Where the line being parsed ends, to find where the source starts
+        let mut offset = match lines.next() {
+            Some((_, header)) if header.trim_ascii_end() == HEADER.as_bytes() => header.len(),
+            _ => return Err(ReproError::BadHeader),
+        };
+
+        let mut repro = Repro::default();
+        for (line_number, line) in lines.by_ref() {
+            let line_number = line_number + 1;
+            offset += line.len();
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            let Ok(line) = std::str::from_utf8(line) else {
+                return Err(ReproError::BadLine(line_number));
+            };
+            if line == "source:" {
+                repro.source = bytes[offset..].to_vec();
+                return Ok(repro);
+            }
+
+            let unescape_text = |escaped: &str| {
+                unescape(escaped)
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .ok_or(ReproError::BadEscape(line_number))
+            };
+            if let Some(arg) = line.strip_prefix("arg: ") {
+                repro.args.push(unescape_text(arg)?);
+            } else if let Some(input) = line.strip_prefix("input: ") {
+                repro.input = unescape(input).ok_or(ReproError::BadEscape(line_number))?;
+            } else if let Some(command) = line.strip_prefix("command: ") {
+                repro.commands.push(unescape_text(command)?);
+            } else {
+                return Err(ReproError::BadLine(line_number));
+            }
+        }
+        Err(ReproError::MissingSource)
+    }
+
+    /// Returns the reproduction in its text format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut text = format!("{HEADER}\n");
+        for arg in &self.args {
+            text.push_str(&format!("arg: {}\n", arg.as_bytes().escape_ascii()));
+        }
+        if !self.input.is_empty() {
+            text.push_str(&format!("input: {}\n", self.input.escape_ascii()));
+        }
+        for command in &self.commands {
+            text.push_str(&format!("command: {}\n", command.as_bytes().escape_ascii()));
+        }
+        text.push_str("source:\n");
+        let mut bytes = text.into_bytes();
+        bytes.extend_from_slice(&self.source);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrips_the_source_byte_for_byte() {
+        let repro = Repro {
+            args: vec!["brainstorm".to_string(), "-d".to_string()],
+            input: b"a\n\xff".to_vec(),
+            commands: vec!["step 3".to_string(), "repro a b".to_string()],
+            source: b";!eof set-zero\n+[>,.<-] \xfe comment\r\nsource:\n".to_vec(),
+        };
+        assert_eq!(Repro::parse(&repro.to_bytes()).unwrap(), repro);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let text = b"brainstorm-repro 1\nsource:\n+";
+        assert!(matches!(Repro::parse(text), Err(ReproError::BadHeader)));
+    }
+}
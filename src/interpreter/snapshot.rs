@@ -0,0 +1,219 @@
+use crate::interpreter::{CellWidth, Interpreter};
+use clap::ValueEnum;
+use std::fmt::Display;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("line {0}: expected `<field>: <value>`")]
+    BadLine(usize),
+    #[error("line {0}: unknown field `{1}`")]
+    UnknownField(usize, String),
+    #[error("line {0}: invalid value")]
+    BadValue(usize),
+    #[error("missing field `{0}`")]
+    MissingField(&'static str),
+    #[error("the tape holds more cells than `tape-size`")]
+    TapeTooLong,
+}
+
+/// A copy of an interpreter's state at some point in its execution, which can be saved to a file
+/// and compared with other snapshots.
+///
+/// The text format has one `<field>: <value>` line per field, with addresses in hex and the tape as
+/// a string of hex cells, with trailing zero cells left out. Every cell takes as many hex digits as
+/// its width needs. The `cell-width` line is left out for 8-bit cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub pc: usize,
+    pub ptr: usize,
+    pub steps: u64,
+    /// The addresses of every breakpoint, enabled or not, in ascending order
+    pub breakpoints: Vec<usize>,
+    pub cell_width: CellWidth,
+    pub tape: Vec<u32>,
+}
+
+/// A single difference between two snapshots, displayed as a line of space separated fields
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SnapshotDifference {
+    Pc(usize, usize),
+    Ptr(usize, usize),
+    Steps(u64, u64),
+    TapeSize(usize, usize),
+    CellWidth(CellWidth, CellWidth),
+    Cell { address: usize, old: u32, new: u32 },
+    BreakpointRemoved(usize),
+    BreakpointAdded(usize),
+}
+
+impl Display for SnapshotDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pc(old, new) => write!(f, "pc {old:#x} {new:#x}"),
+            Self::Ptr(old, new) => write!(f, "ptr {old:#x} {new:#x}"),
+            Self::Steps(old, new) => write!(f, "steps {old} {new}"),
+            Self::TapeSize(old, new) => write!(f, "tape-size {old} {new}"),
+            Self::CellWidth(old, new) => write!(f, "cell-width {old} {new}"),
+            Self::Cell { address, old, new } => {
+                write!(f, "cell {address:#x} {old:#04x} {new:#04x}")
+            }
+            Self::BreakpointRemoved(address) => write!(f, "breakpoint -{address:#x}"),
+            Self::BreakpointAdded(address) => write!(f, "breakpoint +{address:#x}"),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Lists every difference between this snapshot and `other`, treating this one as the older
+    pub fn diff(&self, other: &Snapshot) -> Vec<SnapshotDifference> {
+        let mut differences = Vec::new();
+        if self.pc != other.pc {
+            differences.push(SnapshotDifference::Pc(self.pc, other.pc));
+        }
+        if self.ptr != other.ptr {
+            differences.push(SnapshotDifference::Ptr(self.ptr, other.ptr));
+        }
+        if self.steps != other.steps {
+            differences.push(SnapshotDifference::Steps(self.steps, other.steps));
+        }
+        if self.tape.len() != other.tape.len() {
+            differences.push(SnapshotDifference::TapeSize(
+                self.tape.len(),
+                other.tape.len(),
+            ));
+        }
+        if self.cell_width != other.cell_width {
+            differences.push(SnapshotDifference::CellWidth(
+                self.cell_width,
+                other.cell_width,
+            ));
+        }
+        for address in 0..self.tape.len().max(other.tape.len()) {
+            let old = self.tape.get(address).copied().unwrap_or(0);
+            let new = other.tape.get(address).copied().unwrap_or(0);
+            if old != new {
+                differences.push(SnapshotDifference::Cell { address, old, new });
+            }
+        }
+        for &address in &self.breakpoints {
+            if !other.breakpoints.contains(&address) {
+                differences.push(SnapshotDifference::BreakpointRemoved(address));
+            }
+        }
+        for &address in &other.breakpoints {
+            if !self.breakpoints.contains(&address) {
+                differences.push(SnapshotDifference::BreakpointAdded(address));
+            }
+        }
+        differences
+    }
+
+    /// Parses a snapshot from its text format
+    pub fn parse(text: &str) -> Result<Snapshot, SnapshotError> {
+        let (mut pc, mut ptr, mut steps, mut breakpoints, mut tape_size, mut tape) =
+            (None, None, None, None, None, None);
+        let mut cell_width = CellWidth::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let (field, value) = line
+                .split_once(':')
+                .ok_or(SnapshotError::BadLine(line_number))?;
+            let value = value.trim();
+            let bad_value = || SnapshotError::BadValue(line_number);
+            match field {
+                "pc" => pc = Some(parse_hex(value).ok_or_else(bad_value)?),
+                "ptr" => ptr = Some(parse_hex(value).ok_or_else(bad_value)?),
+                "steps" => steps = Some(value.parse().map_err(|_| bad_value())?),
+                "breakpoints" => {
+                    let addresses: Option<Vec<_>> =
+                        value.split_whitespace().map(parse_hex).collect();
+                    breakpoints = Some(addresses.ok_or_else(bad_value)?);
+                }
+                "tape-size" => tape_size = Some(value.parse().map_err(|_| bad_value())?),
+                "cell-width" => {
+                    cell_width = CellWidth::from_str(value, false).map_err(|_| bad_value())?
+                }
+                // Cells are only read once the cell width, which may come later, is known
+                "tape" => tape = Some((value, line_number)),
+                field => return Err(SnapshotError::UnknownField(line_number, field.to_string())),
+            }
+        }
+
+        let tape_size: usize = tape_size.ok_or(SnapshotError::MissingField("tape-size"))?;
+        let (tape, line_number) = tape.ok_or(SnapshotError::MissingField("tape"))?;
+        let digits = cell_width.hex_digits();
+        if tape.len() % digits != 0 {
+            return Err(SnapshotError::BadValue(line_number));
+        }
+        let cells: Option<Vec<_>> = (0..tape.len())
+            .step_by(digits)
+            .map(|i| u32::from_str_radix(tape.get(i..i + digits)?, 16).ok())
+            .collect();
+        let mut tape = cells.ok_or(SnapshotError::BadValue(line_number))?;
+        if tape.len() > tape_size {
+            return Err(SnapshotError::TapeTooLong);
+        }
+        tape.resize(tape_size, 0);
+        Ok(Snapshot {
+            pc: pc.ok_or(SnapshotError::MissingField("pc"))?,
+            ptr: ptr.ok_or(SnapshotError::MissingField("ptr"))?,
+            steps: steps.ok_or(SnapshotError::MissingField("steps"))?,
+            breakpoints: breakpoints.ok_or(SnapshotError::MissingField("breakpoints"))?,
+            cell_width,
+            tape,
+        })
+    }
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pc: {:#x}", self.pc)?;
+        writeln!(f, "ptr: {:#x}", self.ptr)?;
+        writeln!(f, "steps: {}", self.steps)?;
+        let breakpoints: Vec<_> = self
+            .breakpoints
+            .iter()
+            .map(|address| format!("{address:#x}"))
+            .collect();
+        writeln!(f, "breakpoints: {}", breakpoints.join(" "))?;
+        writeln!(f, "tape-size: {}", self.tape.len())?;
+        if self.cell_width != CellWidth::Bits8 {
+            writeln!(f, "cell-width: {}", self.cell_width)?;
+        }
+        let used = self
+            .tape
+            .iter()
+            .rposition(|&cell| cell != 0)
+            .map_or(0, |last| last + 1);
+        write!(f, "tape: ")?;
+        let digits = self.cell_width.hex_digits();
+        for cell in &self.tape[..used] {
+            write!(f, "{cell:0digits$x}")?;
+        }
+        writeln!(f)
+    }
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+    /// Takes a snapshot of the interpreter's current state
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            pc: self.pc,
+            ptr: self.ptr,
+            steps: self.steps,
+            breakpoints: self
+                .breakpoints()
+                .into_iter()
+                .map(|(address, _)| address)
+                .collect(),
+            cell_width: self.cell_width,
+            tape: self.tape.clone(),
+        }
+    }
+}
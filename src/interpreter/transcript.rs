@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TranscriptError {
+    #[error("line {0}: expected `input: ` or `output: `")]
+    BadLine(usize),
+    #[error("line {0}: invalid escape sequence")]
+    BadEscape(usize),
+}
+
+/// Which stream of a program's I/O some bytes went through
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Stream {
+    Input,
+    Output,
+}
+
+/// A record of the input fed to a program and the output it produced, in the order they happened.
+///
+/// The text format has one line per run of bytes on the same stream, such as `input: 12\n` or
+/// `output: Hello World!\n`, with bytes escaped like Rust byte strings (`\n`, `\x00`, ...).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Transcript {
+    pub events: Vec<(Stream, Vec<u8>)>,
+}
+
+impl Transcript {
+    /// Appends bytes to the transcript, merging them with the last event if it's on the same
+    /// stream
+    pub fn record(&mut self, stream: Stream, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        match self.events.last_mut() {
+            Some((last, last_bytes)) if *last == stream => last_bytes.extend_from_slice(bytes),
+            _ => self.events.push((stream, bytes.to_vec())),
+        }
+    }
+
+    /// Returns every byte recorded on a stream
+    pub fn bytes(&self, stream: Stream) -> Vec<u8> {
+        self.events
+            .iter()
+            .filter(|(event, _)| *event == stream)
+            .flat_map(|(_, bytes)| bytes.iter().copied())
+            .collect()
+    }
+
+    /// Parses a transcript from its text format
+    pub fn parse(text: &str) -> Result<Transcript, TranscriptError> {
+        let mut transcript = Transcript::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let (stream, escaped) = if let Some(escaped) = line.strip_prefix("input: ") {
+                (Stream::Input, escaped)
+            } else if let Some(escaped) = line.strip_prefix("output: ") {
+                (Stream::Output, escaped)
+            } else {
+                return Err(TranscriptError::BadLine(line_number));
+            };
+            let bytes = unescape(escaped).ok_or(TranscriptError::BadEscape(line_number))?;
+            transcript.record(stream, &bytes);
+        }
+        Ok(transcript)
+    }
+}
+
+impl Display for Transcript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (stream, bytes) in &self.events {
+            let name = match stream {
+                Stream::Input => "input",
+                Stream::Output => "output",
+            };
+            writeln!(f, "{name}: {}", bytes.escape_ascii())?;
+        }
+        Ok(())
+    }
+}
+
+/// Reverses `escape_ascii`
+pub(crate) fn unescape(escaped: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = escaped.bytes();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        bytes.push(match chars.next()? {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'x' => {
+                let digits = [chars.next()?, chars.next()?];
+                u8::from_str_radix(std::str::from_utf8(&digits).ok()?, 16).ok()?
+            }
+            byte @ (b'\\' | b'\'' | b'"') => byte,
+            _ => return None,
+        });
+    }
+    Some(bytes)
+}
+
+/// Wraps a reader, recording every byte read from it in a transcript
+pub struct RecordingReader<R: Read> {
+    inner: R,
+    transcript: Rc<RefCell<Transcript>>,
+}
+
+impl<R: Read> RecordingReader<R> {
+    pub fn new(inner: R, transcript: Rc<RefCell<Transcript>>) -> RecordingReader<R> {
+        RecordingReader { inner, transcript }
+    }
+}
+
+impl<R: Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.transcript
+            .borrow_mut()
+            .record(Stream::Input, &buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Wraps a writer, recording every byte written to it in a transcript
+pub struct RecordingWriter<W: Write> {
+    inner: W,
+    transcript: Rc<RefCell<Transcript>>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    pub fn new(inner: W, transcript: Rc<RefCell<Transcript>>) -> RecordingWriter<W> {
+        RecordingWriter { inner, transcript }
+    }
+}
+
+impl<W: Write> Write for RecordingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.transcript
+            .borrow_mut()
+            .record(Stream::Output, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,5 @@
+//! Brainstorm is a brainfuck interpreter and debugger. The interpreter and parser are exposed as a
+//! library so they can be embedded in other programs.
+
+pub mod interpreter;
+pub mod parser;
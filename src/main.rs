@@ -1,75 +1,1692 @@
-use crate::interpreter::debugger::Debugger;
-use crate::interpreter::{EofBehaviour, Interpreter};
-use crate::parser::Program;
+use brainstorm::interpreter::asciicast::{Asciicast, AsciicastWriter};
+use brainstorm::interpreter::debugger::Debugger;
+use brainstorm::interpreter::fixture::Fixture;
+use brainstorm::interpreter::options::RunOptions;
+use brainstorm::interpreter::repro::Repro;
+use brainstorm::interpreter::snapshot::Snapshot;
+use brainstorm::interpreter::transcript::{RecordingReader, RecordingWriter, Stream, Transcript};
+use brainstorm::interpreter::{
+    CellWidth, Endianness, EofBehaviour, FixedPoint, Interpreter, InterpreterError, WideOutput,
+};
+use brainstorm::parser::dialect::{BuiltinDialect, Dialect};
+use brainstorm::parser::{Lints, ParserError, Program, Token};
 use clap::Parser;
+use std::cell::RefCell;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
-
-mod interpreter;
-mod parser;
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
+use std::ops::RangeInclusive;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 /// A brainfuck interpreter and debugger
 #[derive(Parser, Debug)]
 #[command(version, about, long_about)]
 struct Args {
     /// Sets the program file to run
+    #[arg(short, long, required_unless_present_any = ["run_compiled", "format", "diff_snapshots", "diff_programs", "verify_fixture", "repro", "run_dir"])]
+    program_file: Option<PathBuf>,
+
+    /// Formats the given program file, indenting loops by their nesting depth, and prints it
+    /// instead of running a program
+    #[arg(long, conflicts_with_all = ["program_file", "run_compiled"])]
+    format: Option<PathBuf>,
+
+    /// Compares two snapshots saved with the debugger's `save-snapshot` command instead of running
+    /// a program, printing one line per difference (`pc`, `ptr`, `steps`, `tape-size`,
+    /// `cell-width`, `cell` or `breakpoint`, followed by the old and new values). Exits with 1 if
+    /// they differ
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["program_file", "run_compiled", "format"])]
+    diff_snapshots: Option<Vec<PathBuf>>,
+
+    /// Compares the disassembly of two program files instead of running a program, printing a
+    /// unified diff of their tokens. Exits with 1 if they differ
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["program_file", "run_compiled", "format", "diff_snapshots"])]
+    diff_programs: Option<Vec<PathBuf>>,
+
+    /// Runs the program on its input and saves a fixture to the given directory, holding the
+    /// source, the input, the output, the step count and a hash of the final tape, instead of
+    /// running it normally
+    #[arg(long, conflicts_with_all = ["run_compiled", "dialect", "map", "debugger", "attachable"])]
+    gen_fixture: Option<PathBuf>,
+
+    /// Runs the program of a fixture saved with `--gen-fixture` again, and checks that it still
+    /// behaves as recorded. Exits with 1 if it doesn't
+    #[arg(long, conflicts_with_all = ["program_file", "run_compiled", "format", "diff_snapshots"])]
+    verify_fixture: Option<PathBuf>,
+
+    /// Reproduces a debugger session saved with the debugger's `repro` command, starting the
+    /// debugger with the same program, options and input, and entering the same commands
+    #[arg(long, conflicts_with_all = ["program_file", "run_compiled", "format", "diff_snapshots", "verify_fixture"])]
+    repro: Option<PathBuf>,
+
+    /// Makes `--format` overwrite the program file instead of printing it
+    #[arg(long, default_value_t = false, requires = "format")]
+    in_place: bool,
+
+    /// Compiles the program into a binary file that can be run with `--run-compiled`, instead of
+    /// running it
+    #[arg(long, conflicts_with = "run_compiled")]
+    compile: Option<PathBuf>,
+
+    /// Writes a graph (in the DOT language) of the jumps between the program's units to the given
+    /// file, instead of running it
+    #[arg(long)]
+    emit_unit_graph: Option<PathBuf>,
+
+    /// Writes a disassembly of the program to the given file as tab-separated values, one token
+    /// per line, for use by other tools, instead of running it
+    #[arg(long)]
+    disasm_tsv: Option<PathBuf>,
+
+    /// Splits a program without `;` units into inferred ones: one for every loop at the top level,
+    /// named `loop@<address>`, and one for the code between them, named `code@<address>`. Programs
+    /// with units of their own keep them
+    #[arg(long, default_value_t = false)]
+    auto_units: bool,
+
+    /// Checks whether the program is a copy of a well-known program, such as a classic hello
+    /// world, by the hash of its normalized tokens, instead of running it
+    #[arg(long, default_value_t = false)]
+    identify: bool,
+
+    /// Runs a program previously compiled with `--compile`, instead of a program file
+    #[arg(long, conflicts_with = "program_file")]
+    run_compiled: Option<PathBuf>,
+
+    /// Parses the program as one of the built-in brainfuck dialects
+    #[arg(long, conflicts_with = "map")]
+    dialect: Option<BuiltinDialect>,
+
+    /// Parses the program as a dialect described by a map file, in which each line holds a
+    /// brainfuck command followed by the dialect's command for it (e.g. `+ Ook. Ook.`)
+    #[arg(long)]
+    map: Option<PathBuf>,
+
+    /// Feeds the contents of the given environment variable to the program as input, instead of
+    /// stdin
+    #[arg(long)]
+    input_env: Option<String>,
+
+    /// Feeds the contents of the given files (separated by commas) to the program as input, one
+    /// after another, instead of stdin. EOF is only reached at the end of the last file
+    #[arg(long, value_delimiter = ',', conflicts_with = "input_env")]
+    input_files: Option<Vec<PathBuf>>,
+
+    /// Inserts the given byte (hex) between the files given to `--input-files`
+    #[arg(long, value_parser = parse_hex_byte, requires = "input_files")]
+    input_separator: Option<u8>,
+
+    /// Lists every unclosed [ when the program fails to parse, instead of only the first
+    #[arg(long, default_value_t = false)]
+    parse_trace: bool,
+
+    /// Warns about runs of commands that cancel out, such as `+-` or `<>`, which usually point to
+    /// a mistake
+    #[arg(long, default_value_t = false)]
+    warn_noops: bool,
+
+    /// Warns about loops whose body moves the pointer by a nonzero amount on every iteration,
+    /// which is usually a mistake
+    #[arg(long, default_value_t = false)]
+    warn_drift: bool,
+
+    /// Writes every byte of input the program read, after carriage returns were skipped, to the
+    /// given file once it stops. Feeding the file back with `--input-files` reproduces the run
+    #[arg(long)]
+    save_input: Option<PathBuf>,
+
+    /// Records the program's output, with when it was written, to a file in the asciicast v2
+    /// format, which can be replayed with `asciinema play`
+    #[arg(long)]
+    record_asciicast: Option<PathBuf>,
+
+    /// Records the program's input and output to a transcript file
+    #[arg(long)]
+    record_io: Option<PathBuf>,
+
+    /// Replays the input recorded in a transcript file, and verifies the program's output matches
+    /// the recorded output byte for byte
+    #[arg(long, conflicts_with_all = ["input_env", "input_files", "record_io"])]
+    verify_io: Option<PathBuf>,
+
+    /// Writes the program to the given file as the shortest equivalent brainfuck source, with only
+    /// commands and no whitespace, comments, units or directives, instead of running it
+    #[arg(long, conflicts_with_all = ["debugger", "attachable", "compile"])]
+    minify: Option<PathBuf>,
+
+    /// Makes `--minify` remove dead code first, as `--minimize` does. The result then only
+    /// produces the same output, rather than doing exactly the same
+    #[arg(long, default_value_t = false, requires = "minify")]
+    minify_dead_code: bool,
+
+    /// Writes a smaller version of the program to the given file, with dead code removed, instead
+    /// of running it. The result is checked to produce the same output on the program's input
+    #[arg(long, conflicts_with_all = ["debugger", "attachable", "compile"])]
+    minimize: Option<PathBuf>,
+
+    /// Runs the program with and without optimizations on the same input instead of running it
+    /// normally, and checks that both runs produce the same output and final tape
+    #[arg(long, default_value_t = false, conflicts_with_all = ["debugger", "attachable", "compile"])]
+    verify_opt: bool,
+
+    /// Runs the program on its input with a small tape, growing the tape whenever the program
+    /// moves past its end, and prints the smallest tape size the program needs instead of running
+    /// it normally
+    #[arg(long, default_value_t = false, conflicts_with_all = ["debugger", "attachable", "compile", "tape_size", "ring_tape"])]
+    probe_tape: bool,
+
+    /// Runs the program the given number of times on random inputs instead of running it
+    /// normally, reporting every run that fails, runs out of steps or panics, along with the
+    /// smallest input found that fails the same way. Exits with 1 if any run failed
+    #[arg(long, conflicts_with_all = ["debugger", "attachable", "compile"])]
+    fuzz: Option<u64>,
+
+    /// Sets the seed of the random inputs generated by `--fuzz`, to reproduce a fuzzing session
+    #[arg(long, default_value_t = 0, requires = "fuzz")]
+    fuzz_seed: u64,
+
+    /// Sets how many steps each `--fuzz` run may take before it's reported as not halting
+    #[arg(long, default_value_t = 1_000_000, requires = "fuzz")]
+    fuzz_steps: u64,
+
+    /// Self-tests every `.bf` file in the given directory instead of running a program, printing
+    /// whether each passed and how long it took. Programs run with no input. Exits with 1 if any
+    /// failed
+    #[arg(long, conflicts_with_all = ["program_file", "run_compiled", "format", "debugger", "attachable"])]
+    run_dir: Option<PathBuf>,
+
+    /// Checks the expectations the program declares (such as `;!expect-ptr` or `;;expect:`)
+    /// once it halts, reporting each one that doesn't hold and exiting with 1 if any fail
+    #[arg(long, default_value_t = false, conflicts_with_all = ["debugger", "attachable", "break_on_print"])]
+    self_test: bool,
+
+    /// Sets the size of the tape for the interpreter [default: 65536]
     #[arg(short, long)]
-    program_file: PathBuf,
+    tape_size: Option<usize>,
 
-    /// Sets the size of the tape for the interpreter
-    #[arg(short, long, default_value_t = 1024*64)]
-    tape_size: usize,
+    /// Makes the tape a ring, so that moving past either end wraps around to the other end
+    /// instead of being an error. Note that this changes the meaning of programs that rely on
+    /// the edges of the tape
+    #[arg(long, default_value_t = false)]
+    ring_tape: bool,
+
+    /// Stops the program with an error once it tries to output more than this many bytes
+    #[arg(long)]
+    max_output: Option<u64>,
+
+    /// Sets how `.` turns a cell into output bytes: its lowest byte, every one of its bytes, or
+    /// the UTF-8 encoding of the code point it holds. `low-byte` and `all-bytes` only differ with
+    /// a `--cell-width` wider than 8
+    #[arg(long, default_value = "low-byte")]
+    wide_output: WideOutput,
+
+    /// Sets the byte order `--wide-output all-bytes` writes a cell's bytes in
+    #[arg(long, default_value = "little")]
+    output_endianness: Endianness,
+
+    /// Only writes output while the cell at the given address (hex) is non-zero, discarding it
+    /// otherwise, so a flag cell can switch a program's output on and off
+    #[arg(long, value_parser = parse_hex_address)]
+    output_gate: Option<usize>,
+
+    /// Aborts the program once it runs this many steps in a row without producing any output,
+    /// reporting where it was, to catch programs stuck in a loop
+    #[arg(long, conflicts_with_all = ["debugger", "attachable"])]
+    watchdog_output: Option<u64>,
+
+    /// Writes values into tape cells before the program starts, as comma-separated
+    /// `<address>=<value>` pairs (both hex), such as `0=2a,1=07`. Together with
+    /// `--exit-from-cell`, this lets a program be called like a function
+    #[arg(long, value_delimiter = ',', value_parser = parse_preset_cell)]
+    preset_cells: Vec<(usize, u8)>,
+
+    /// Sets the byte (hex) every tape cell is initialized to [default: 0]
+    #[arg(long, value_parser = parse_hex_byte)]
+    fill_byte: Option<u8>,
 
     /// Sets the behaviour when an input instruction is executed after input has reached end of file
-    #[arg(short, long, default_value_t = EofBehaviour::DontSet)]
-    eof_behaviour: EofBehaviour,
+    /// [default: dont-set]
+    #[arg(short, long)]
+    eof_behaviour: Option<EofBehaviour>,
 
     /// Enables printing the interpreter's internal status on # commands
     #[arg(short = 'i', long, default_value_t = false)]
     print_debug: bool,
 
+    /// Makes # commands skip printing the state when it's identical to the last one printed,
+    /// printing how many times it was skipped once it changes
+    #[arg(long, default_value_t = false)]
+    collapse_state: bool,
+
+    /// Stops the program at the first # command, printing the interpreter's internal status once
+    #[arg(long, default_value_t = false, conflicts_with_all = ["debugger", "attachable"])]
+    break_on_print: bool,
+
+    /// Colors the cells of tape hexdumps on a gradient by their value
+    #[arg(long, default_value_t = false)]
+    heat_colors: bool,
+
+    /// Displays cell values as signed (-128 to 127) rather than unsigned. Arithmetic is unaffected
+    #[arg(long, default_value_t = false)]
+    signed_cells: bool,
+
+    /// Makes the debugger's `examine` command also read the cells starting at the examined
+    /// address as an unsigned fixed-point number, laid out as `<int-cells>.<frac-cells>` (such as
+    /// `2.1`). Cells are big-endian: the most significant one is at the lowest address
+    #[arg(long, value_parser = FixedPoint::parse)]
+    fixed_point: Option<FixedPoint>,
+
+    /// Sets the range of cell values (hex, as in `20-7e`) shown as characters in tape dumps, for
+    /// terminals that can display more than printable ASCII [default: 20-7e]
+    #[arg(long, value_parser = parse_byte_range)]
+    printable_range: Option<RangeInclusive<u8>>,
+
+    /// Sets how many bits every tape cell holds. `+` and `-` wrap around at the cell's width, `,`
+    /// still reads a byte and `.` writes the cell's lowest byte unless `--wide-output` says
+    /// otherwise [default: 8]
+    #[arg(long)]
+    cell_width: Option<CellWidth>,
+
+    /// Makes `+` and `-` wrap within an inclusive range of cell values (hex, as in `0-9` for
+    /// decimal digits) instead of 00-ff: incrementing the highest value gives the lowest, and
+    /// decrementing the lowest gives the highest
+    #[arg(long, value_parser = parse_byte_range)]
+    cell_range: Option<RangeInclusive<u8>>,
+
     /// Enables the interactive debugger
     #[arg(short, long, default_value_t = false)]
     debugger: bool,
+
+    /// Sets a breakpoint at the start of every unit when the debugger starts, so continuing stops
+    /// at each unit in turn
+    #[arg(long, default_value_t = false, requires = "debugger")]
+    break_all_units: bool,
+
+    /// Makes Ctrl-C attach the interactive debugger to the running program instead of exiting
+    #[arg(long, default_value_t = false, conflicts_with = "debugger")]
+    attachable: bool,
+
+    /// Prints the highest tape address the pointer reached once the program stops
+    #[arg(long, default_value_t = false)]
+    report_max_ptr: bool,
+
+    /// Prints the total distance the pointer moved once the program stops
+    #[arg(long, default_value_t = false)]
+    report_travel: bool,
+
+    /// Profiles how many steps run inside each loop, and writes the result to the given file in
+    /// the folded stack format used by flamegraph tools
+    #[arg(long)]
+    profile_folded: Option<PathBuf>,
+
+    /// Logs every write to a tape cell as CSV lines of `step,pc,addr,old,new`, to the given file or
+    /// to stderr if no file is given
+    #[arg(long, num_args = 0..=1)]
+    log_writes: Option<Option<PathBuf>>,
+
+    /// Writes the address of every instruction executed to the given file, one per line, so the
+    /// run can be checked later with `--replay`
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Runs the program while checking that it executes the same instructions, in the same order,
+    /// as recorded in a trace file written with `--trace`. Stops at the first divergence and exits
+    /// with 1. The program must be given the same input as when the trace was recorded
+    #[arg(long, conflicts_with_all = ["debugger", "attachable", "break_on_print"])]
+    replay: Option<PathBuf>,
+
+    /// Prints how many instructions of each kind were executed once the program stops
+    #[arg(long, default_value_t = false)]
+    opcode_stats: bool,
+
+    /// Prints a table summarizing the tape once the program stops
+    #[arg(long, default_value_t = false)]
+    tape_summary: bool,
+
+    /// Prints a hash of the tape once the program stops, to quickly compare memory states across
+    /// runs
+    #[arg(long, default_value_t = false)]
+    hash_tape_on_exit: bool,
+
+    /// Once the program halts, exits with the value of the cell at the given address (hex) as the
+    /// exit code, or of the cell under the pointer if no address is given. Without this flag, the
+    /// exit code is always 0
+    #[arg(long, num_args = 0..=1, value_parser = parse_hex_address)]
+    exit_from_cell: Option<Option<usize>>,
+
+    /// Once the program halts, plays its execution backwards to the start, undoing one step at a
+    /// time (at the pace set by `--delay`). Input and output can't be undone, so only the tape,
+    /// pointer, program counter and the counters that depend on them are rewound. Every step is
+    /// recorded, which uses a lot of memory on long runs
+    #[arg(long, default_value_t = false, conflicts_with_all = ["debugger", "attachable", "break_on_print", "replay"])]
+    reverse: bool,
+
+    /// Sleeps for the given amount of milliseconds between each step, to animate the program's
+    /// output
+    #[arg(long, default_value_t = 0)]
+    delay: u64,
+}
+
+fn parse_hex_byte(s: &str) -> Result<u8, String> {
+    u8::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("expected a hex byte such as 0xAA: {e}"))
+}
+
+fn parse_preset_cell(s: &str) -> Result<(usize, u8), String> {
+    let (address, value) = s
+        .split_once('=')
+        .ok_or("expected a hex address and value such as 40=2a")?;
+    Ok((parse_hex_address(address)?, parse_hex_byte(value)?))
+}
+
+fn parse_byte_range(s: &str) -> Result<RangeInclusive<u8>, String> {
+    let (low, high) = s
+        .split_once('-')
+        .ok_or("expected a range of hex bytes such as 20-7e")?;
+    let (low, high) = (parse_hex_byte(low)?, parse_hex_byte(high)?);
+    if low > high {
+        return Err(format!(
+            "the range is empty, since {low:#04x} > {high:#04x}"
+        ));
+    }
+    Ok(low..=high)
+}
+
+/// Parses the program file at `path`, in the dialect selected on the command line
+fn parse_program(args: &Args, path: &Path) -> Result<Program, String> {
+    let source = std::fs::read(path).map_err(|e| format!("Error opening program file: {e}"))?;
+    parse_program_source(args, &source)
+}
+
+/// Parses a program's source in the dialect selected on the command line
+fn parse_program_source(args: &Args, source: &[u8]) -> Result<Program, String> {
+    let dialect = if let Some(dialect) = args.dialect {
+        Some(Dialect::builtin(dialect))
+    } else if let Some(map) = &args.map {
+        let dialect = File::open(map)
+            .map_err(ParserError::from)
+            .and_then(|file| Dialect::from_map(BufReader::new(file)))
+            .map_err(|e| format!("Error reading dialect map: {e}"))?;
+        Some(dialect)
+    } else {
+        None
+    };
+
+    parse_source(args, BufReader::new(source), dialect.as_ref())
+}
+
+/// Parses a program's source with the options selected on the command line
+fn parse_source(
+    args: &Args,
+    source: BufReader<impl Read>,
+    dialect: Option<&Dialect>,
+) -> Result<Program, String> {
+    let parse_print = args.print_debug || args.break_on_print;
+    Program::parse(
+        source,
+        parse_print,
+        Lints {
+            noops: args.warn_noops,
+            drift: args.warn_drift,
+        },
+        dialect,
+    )
+    .map_err(|e| match e {
+        ParserError::MissingClose { open } if args.parse_trace => {
+            let mut message = format!(
+                "Error parsing program: {} [ have no matching ]:",
+                open.len()
+            );
+            for position in open {
+                message.push_str(&format!("\n  [ at {position}"));
+            }
+            message
+        }
+        e => format!("Error parsing program: {e}"),
+    })
+}
+
+fn parse_hex_address(s: &str) -> Result<usize, String> {
+    usize::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("expected a hex address such as 0x40: {e}"))
+}
+
+/// Opens the source of the program's input selected on the command line
+fn input_source(args: &Args) -> Result<Box<dyn Read>, String> {
+    if let Some(name) = &args.input_env {
+        let value = std::env::var(name)
+            .map_err(|e| format!("Error reading input from environment variable {name}: {e}"))?;
+        return Ok(Box::new(Cursor::new(value.into_bytes())));
+    }
+    if let Some(paths) = &args.input_files {
+        let mut input: Box<dyn Read> = Box::new(std::io::empty());
+        for (index, path) in paths.iter().enumerate() {
+            let file = File::open(path)
+                .map_err(|e| format!("Error opening input file {}: {e}", path.display()))?;
+            if index > 0
+                && let Some(separator) = args.input_separator
+            {
+                input = Box::new(input.chain(Cursor::new([separator])));
+            }
+            input = Box::new(input.chain(BufReader::new(file)));
+        }
+        return Ok(input);
+    }
+    Ok(Box::new(BufReader::new(std::io::stdin())))
+}
+
+/// Loads a session saved with the debugger's `repro` command, along with the arguments it was
+/// started with
+fn load_repro(path: &Path) -> Result<(Args, Repro), String> {
+    let repro = std::fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| Repro::parse(&bytes).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Error reading reproduction: {e}"))?;
+    let mut args = Args::try_parse_from(&repro.args)
+        .map_err(|e| format!("Error in the reproduction's arguments: {e}"))?;
+    args.debugger = true;
+    Ok((args, repro))
 }
 
 fn main() {
     let args = Args::parse();
+    let (args, repro) = match &args.repro {
+        Some(path) => match load_repro(path) {
+            Ok((args, repro)) => (args, Some(repro)),
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        },
+        None => (args, None),
+    };
+
+    if let Some(path) = &args.format {
+        format(&args, path);
+        return;
+    }
+
+    if let Some(paths) = &args.diff_snapshots {
+        diff_snapshots(&paths[0], &paths[1]);
+    }
+
+    if let Some(paths) = &args.diff_programs {
+        diff_programs(&args, &paths[0], &paths[1]);
+    }
+
+    if let Some(dir) = &args.run_dir {
+        if !run_dir(&args, dir) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(dir) = &args.verify_fixture {
+        if !verify_fixture(dir) {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let file = match File::open(args.program_file) {
-        Ok(f) => f,
+    // The source as it was read, so the debugger's `repro` command can save it unchanged
+    let mut source = None;
+    let program = match (&args.run_compiled, &args.program_file) {
+        _ if let Some(repro) = &repro => {
+            source = Some(repro.source.clone());
+            parse_program_source(&args, &repro.source)
+        }
+        (Some(path), _) => std::fs::read(path)
+            .map_err(ParserError::from)
+            .and_then(|bytes| Program::from_bytes(&bytes))
+            .map_err(|e| format!("Error loading compiled program: {e}")),
+        (None, Some(path)) => std::fs::read(path)
+            .map_err(|e| format!("Error opening program file: {e}"))
+            .and_then(|bytes| {
+                let program = parse_program_source(&args, &bytes);
+                source = Some(bytes);
+                program
+            }),
+        (None, None) => unreachable!("clap requires a program file to run"),
+    };
+    let mut program = match program {
+        Ok(program) => program,
         Err(e) => {
-            eprintln!("Error opening program file: {e}");
+            eprintln!("{e}");
             return;
         }
     };
 
-    let reader = BufReader::new(file);
-    let program = match Program::parse(reader, args.print_debug) {
-        Ok(program) => program,
-        Err(e) => {
-            eprintln!("Error parsing program: {e}");
+    if args.auto_units && !program.infer_units() {
+        eprintln!("Warning: the program has its own units, so none were inferred");
+    }
+
+    for warning in &program.warnings {
+        eprintln!("Warning: {warning}");
+    }
+
+    // Options given on the command line take precedence over the program's own directives
+    let tape_size = args
+        .tape_size
+        .or(program.options.tape_size)
+        .unwrap_or(1024 * 64);
+    let fill_byte = args.fill_byte.or(program.options.fill_byte).unwrap_or(0);
+    let eof_behaviour = args
+        .eof_behaviour
+        .or(program.options.eof_behaviour)
+        .unwrap_or(EofBehaviour::DontSet);
+
+    if let Some(Some(address)) = args.exit_from_cell
+        && address >= tape_size
+    {
+        eprintln!("Exit cell {address:#x} is outside of the tape");
+        return;
+    }
+
+    if let Some(path) = &args.compile {
+        if let Err(e) = std::fs::write(path, program.to_bytes()) {
+            eprintln!("Error writing compiled program: {e}");
+        }
+        return;
+    }
+
+    if let Some(path) = &args.emit_unit_graph {
+        if let Err(e) = std::fs::write(path, program.unit_graph_dot()) {
+            eprintln!("Error writing unit graph: {e}");
+        }
+        return;
+    }
+
+    if let Some(path) = &args.disasm_tsv {
+        if let Err(e) = std::fs::write(path, program.disasm_tsv()) {
+            eprintln!("Error writing disassembly: {e}");
+        }
+        return;
+    }
+
+    if args.identify {
+        match program.identify() {
+            Some(known) => println!("Identified as {}, from {}", known.name, known.origin),
+            None => println!(
+                "Not a known program (token hash {:016x})",
+                program.token_hash()
+            ),
+        }
+        return;
+    }
+
+    if let Some(path) = &args.minify {
+        let program = if args.minify_dead_code {
+            program.minimize(fill_byte)
+        } else {
+            program
+        };
+        if let Err(e) = std::fs::write(path, program.to_minified_source()) {
+            eprintln!("Error writing minified program: {e}");
+        }
+        return;
+    }
+
+    if let Some(path) = &args.minimize {
+        if !minimize(&args, program, path, tape_size, fill_byte, eof_behaviour) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.verify_opt {
+        if !verify_opt(&args, program, tape_size, fill_byte, eof_behaviour) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(runs) = args.fuzz {
+        if !fuzz(&args, program, runs, tape_size, fill_byte, eof_behaviour) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.probe_tape {
+        if !probe_tape(&args, program, fill_byte, eof_behaviour) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(dir) = &args.gen_fixture {
+        if !gen_fixture(&args, dir, tape_size, fill_byte, eof_behaviour) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let replay_trace = match &args.replay {
+        Some(path) => match read_trace(path) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                eprintln!("Error reading trace: {e}");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let expected = match &args.verify_io {
+        Some(path) => match std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Transcript::parse(&text).map_err(|e| e.to_string()))
+        {
+            Ok(transcript) => Some(transcript),
+            Err(e) => {
+                eprintln!("Error reading transcript: {e}");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let program_reads_stdin = expected.is_none()
+        && repro.is_none()
+        && args.input_env.is_none()
+        && args.input_files.is_none();
+    let input: Box<dyn Read> = match (&expected, &repro) {
+        (Some(expected), _) => Box::new(Cursor::new(expected.bytes(Stream::Input))),
+        (None, Some(repro)) => Box::new(Cursor::new(repro.input.clone())),
+        (None, None) => match input_source(&args) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        },
+    };
+    // Output is only buffered when nothing else is printed while the program runs, since the
+    // debugger's output has to interleave with the program's
+    let buffered_output = !(args.debugger || args.attachable || args.break_on_print);
+    let output: Box<dyn Write> = match &expected {
+        Some(_) => Box::new(std::io::sink()),
+        None if buffered_output => Box::new(BufWriter::new(std::io::stdout())),
+        None => Box::new(std::io::stdout()),
+    };
+
+    // I/O is recorded when a transcript is being either recorded or verified, or when the output
+    // is self-tested
+    let transcript = Rc::new(RefCell::new(Transcript::default()));
+    let (input, output): (Box<dyn Read>, Box<dyn Write>) =
+        if args.record_io.is_some() || expected.is_some() || args.self_test {
+            (
+                Box::new(RecordingReader::new(input, transcript.clone())),
+                Box::new(RecordingWriter::new(output, transcript.clone())),
+            )
+        } else {
+            (input, output)
+        };
+    let cast = args
+        .record_asciicast
+        .as_ref()
+        .map(|_| Rc::new(RefCell::new(Asciicast::for_terminal())));
+    let output: Box<dyn Write> = match &cast {
+        Some(cast) => Box::new(AsciicastWriter::new(output, cast.clone())),
+        None => output,
+    };
+    let finish_recordings = || {
+        if let (Some(path), Some(cast)) = (&args.record_asciicast, &cast)
+            && let Err(e) = std::fs::write(path, cast.borrow().to_string())
+        {
+            eprintln!("Error writing asciicast: {e}");
+        }
+        if !finish_transcript(&args, &transcript.borrow(), expected.as_ref()) {
+            std::process::exit(1);
+        }
+    };
+
+    let mut interpreter =
+        Interpreter::new(program, tape_size, fill_byte, eof_behaviour, input, output);
+    if let Err(e) = configure_run(&args, &mut interpreter) {
+        eprintln!("{e}");
+        return;
+    }
+    interpreter.set_input_recording(args.save_input.is_some());
+    interpreter.set_buffered_output(buffered_output);
+    interpreter.set_delay(Duration::from_millis(args.delay));
+    interpreter.set_heat_colors(args.heat_colors);
+    interpreter.set_collapse_state(args.collapse_state);
+    interpreter.set_signed_cells(args.signed_cells);
+    interpreter.set_fixed_point(args.fixed_point);
+    if let Some(printable) = &args.printable_range {
+        interpreter.set_printable_range(printable.clone());
+    }
+    interpreter.set_output_watchdog(args.watchdog_output);
+    interpreter.set_loop_profiling(args.profile_folded.is_some());
+    interpreter.set_pc_profiling(args.opcode_stats);
+    interpreter.set_undo_log(args.reverse);
+    let write_log: Option<Box<dyn Write>> = match &args.log_writes {
+        Some(Some(path)) => match File::create(path) {
+            Ok(file) => Some(Box::new(file)),
+            Err(e) => {
+                eprintln!("Error creating write log: {e}");
+                return;
+            }
+        },
+        Some(None) => Some(Box::new(std::io::stderr())),
+        None => None,
+    };
+    if let Err(e) = interpreter.set_write_log(write_log) {
+        eprintln!("Error writing write log: {e}");
+        return;
+    }
+
+    if let Some(path) = &args.trace {
+        match File::create(path) {
+            Ok(file) => interpreter.set_trace(Some(Box::new(file))),
+            Err(e) => {
+                eprintln!("Error creating trace: {e}");
+                return;
+            }
+        }
+    }
+
+    if args.debugger {
+        if args.break_all_units {
+            interpreter.break_all_units();
+        }
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let handler_interrupt = interrupt.clone();
+        if let Err(e) = ctrlc::set_handler(move || handler_interrupt.store(true, Ordering::Relaxed))
+        {
+            eprintln!("Error installing Ctrl-C handler: {e}");
             return;
         }
+
+        let mut debugger = Debugger::new(interpreter);
+        debugger.set_interrupt(interrupt);
+        debugger.set_program_reads_stdin(program_reads_stdin);
+        if let Some(source) = source {
+            debugger.set_source(source);
+        }
+        match repro {
+            Some(repro) => {
+                debugger.set_invocation(repro.args);
+                debugger.set_script(repro.commands);
+            }
+            None => debugger.set_invocation(std::env::args().collect()),
+        }
+        debugger.run();
+        save_input(&args, debugger.interpreter());
+        finish_recordings();
+    } else {
+        let mut diverged = false;
+        let result = if args.attachable {
+            let interrupt = Arc::new(AtomicBool::new(false));
+            let handler_interrupt = interrupt.clone();
+            if let Err(e) =
+                ctrlc::set_handler(move || handler_interrupt.store(true, Ordering::Relaxed))
+            {
+                eprintln!("Error installing Ctrl-C handler: {e}");
+                return;
+            }
+
+            match interpreter.run_interruptible(&interrupt) {
+                Ok(true) => {
+                    println!();
+                    println!("Interrupted, attaching debugger");
+                    let mut debugger = Debugger::new(interpreter);
+                    debugger.set_interrupt(interrupt);
+                    debugger.set_program_reads_stdin(program_reads_stdin);
+                    debugger.set_invocation(std::env::args().collect());
+                    if let Some(source) = source {
+                        debugger.set_source(source);
+                    }
+                    debugger.run();
+                    save_input(&args, debugger.interpreter());
+                    finish_recordings();
+                    return;
+                }
+                result => result.map(|_| ()),
+            }
+        } else if let Some(trace) = &replay_trace {
+            replay(&mut interpreter, trace).map(|matched| diverged = !matched)
+        } else if args.break_on_print {
+            interpreter.run_to_print().map(|reached| {
+                if reached {
+                    interpreter.print_state();
+                }
+            })
+        } else {
+            interpreter.run()
+        };
+
+        if args.reverse && result.is_ok() {
+            reverse(&mut interpreter, Duration::from_millis(args.delay));
+        }
+
+        if let Err(e) = interpreter.flush_output() {
+            eprintln!("Error writing output: {e}");
+        }
+        if let Err(e) = interpreter.flush_logs() {
+            eprintln!("Error writing logs: {e}");
+        }
+        report(&args, &interpreter);
+        save_input(&args, &interpreter);
+        finish_recordings();
+        if diverged {
+            std::process::exit(1);
+        }
+        if args.self_test
+            && !self_test(
+                &interpreter,
+                result.is_ok(),
+                &transcript.borrow().bytes(Stream::Output),
+            )
+        {
+            std::process::exit(1);
+        }
+        match result {
+            Ok(()) => {
+                if let Some(address) = args.exit_from_cell {
+                    let address = address.unwrap_or(interpreter.ptr());
+                    std::process::exit(interpreter.tape()[address] as i32);
+                }
+            }
+            Err(InterpreterError::OutputWatchdog(steps)) => {
+                let unit = &interpreter.program().units[interpreter.current_unit()];
+                eprintln!(
+                    "Warning: no output for {steps} steps, aborted at {} in unit {}",
+                    interpreter.format_address(interpreter.pc()),
+                    unit.description
+                );
+                std::process::exit(1);
+            }
+            Err(e) => eprintln!("Error running interpreter: {e}"),
+        }
+    }
+}
+
+/// Prints the differences between two snapshot files, and exits with 0 if there are none, 1 if
+/// there are some, or 2 if the snapshots couldn't be read
+fn diff_snapshots(a: &Path, b: &Path) -> ! {
+    let load = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Snapshot::parse(&text).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Error reading snapshot {}: {e}", path.display()))
+    };
+    let (a, b) = match (load(a), load(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let differences = a.diff(&b);
+    for difference in &differences {
+        println!("{difference}");
+    }
+    std::process::exit(if differences.is_empty() { 0 } else { 1 });
+}
+
+/// Prints a diff of the disassembly of two program files, and exits with 0 if they have the same
+/// tokens, 1 if they don't, or 2 if either couldn't be parsed
+fn diff_programs(args: &Args, a: &Path, b: &Path) -> ! {
+    let load =
+        |path: &Path| parse_program(args, path).map_err(|e| format!("{e} (in {})", path.display()));
+    let (program_a, program_b) = match (load(a), load(b)) {
+        (Ok(program_a), Ok(program_b)) => (program_a, program_b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let diff = program_a.diff_disassembly(&program_b);
+    if !diff.is_empty() {
+        println!("--- {}", a.display());
+        println!("+++ {}", b.display());
+        print!("{diff}");
+    }
+    std::process::exit(if diff.is_empty() { 0 } else { 1 });
+}
+
+/// Records the program's behaviour on its input as a fixture in `dir`. Returns false if it
+/// couldn't.
+fn gen_fixture(
+    args: &Args,
+    dir: &Path,
+    tape_size: usize,
+    fill_byte: u8,
+    eof_behaviour: EofBehaviour,
+) -> bool {
+    let fixture = args
+        .program_file
+        .as_ref()
+        .ok_or("--gen-fixture needs a program file".to_string())
+        .and_then(|path| {
+            std::fs::read(path).map_err(|e| format!("Error reading program file: {e}"))
+        })
+        .and_then(|source| Ok((source, read_input(args)?)))
+        .and_then(|(source, input)| {
+            let options = run_options(args);
+            Fixture::record(source, input, tape_size, fill_byte, eof_behaviour, options)
+                .map_err(|e| e.to_string())
+        });
+    let result = fixture.and_then(|fixture| {
+        fixture
+            .save(dir)
+            .map(|_| fixture)
+            .map_err(|e| format!("Error saving fixture: {e}"))
+    });
+    match result {
+        Ok(fixture) => {
+            eprintln!(
+                "Saved fixture to {} ({} bytes of output, {} steps)",
+                dir.display(),
+                fixture.output.len(),
+                fixture.steps
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            false
+        }
+    }
+}
+
+/// Checks that the program of the fixture in `dir` still behaves as recorded, printing every
+/// mismatch. Returns false if there are any, or if the fixture couldn't be loaded.
+fn verify_fixture(dir: &Path) -> bool {
+    let mismatches = Fixture::load(dir).and_then(|fixture| fixture.verify());
+    match mismatches {
+        Ok(mismatches) if mismatches.is_empty() => {
+            eprintln!("Fixture {} matches", dir.display());
+            true
+        }
+        Ok(mismatches) => {
+            for mismatch in mismatches {
+                eprintln!("Fixture mismatch: {mismatch}");
+            }
+            false
+        }
+        Err(e) => {
+            eprintln!("Error loading fixture {}: {e}", dir.display());
+            false
+        }
+    }
+}
+
+/// Plays a halted program's execution backwards to its start, printing every step undone, and
+/// the interpreter's state once it's back at the start
+fn reverse<R: Read, W: Write>(interpreter: &mut Interpreter<R, W>, delay: Duration) {
+    println!();
+    println!("Reversing {} steps", interpreter.undoable_steps());
+    while interpreter.step_back() {
+        let ptr = interpreter.ptr();
+        println!(
+            "step {}: undid {} at {:#x}, cell {ptr:#x} = {:#04x}",
+            interpreter.steps(),
+            interpreter.program().tokens[interpreter.pc()],
+            interpreter.pc(),
+            interpreter.tape()[ptr]
+        );
+        std::thread::sleep(delay);
+    }
+    interpreter.print_state();
+}
+
+/// Reads a trace written with `--trace`
+fn read_trace(path: &Path) -> Result<Vec<usize>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| {
+            parse_hex_address(line).map_err(|e| format!("line {}: {e}", index + 1))
+        })
+        .collect()
+}
+
+/// Runs the program, checking before every step that the instruction about to be executed is the
+/// next one in the trace, and that the program halts where the trace ends. Returns Ok(false) after
+/// reporting the first divergence, if there is one.
+fn replay<R: Read, W: Write>(
+    interpreter: &mut Interpreter<R, W>,
+    trace: &[usize],
+) -> Result<bool, InterpreterError> {
+    for &expected in trace {
+        if interpreter.pc() != expected {
+            eprintln!(
+                "Replay diverged at step {}: the trace executes {expected:#x}, but the program is at {:#x}",
+                interpreter.steps(),
+                interpreter.pc()
+            );
+            return Ok(false);
+        }
+        interpreter.step()?;
+    }
+    if interpreter.program().tokens[interpreter.pc()] != Token::Eof {
+        eprintln!(
+            "Replay diverged at step {}: the trace ends, but the program is still running at {:#x}",
+            interpreter.steps(),
+            interpreter.pc()
+        );
+        return Ok(false);
+    }
+    eprintln!("Replay matches the trace ({} steps)", trace.len());
+    Ok(true)
+}
+
+/// Writes the recorded I/O transcript, or verifies it against the expected transcript, as
+/// requested on the command line. Returns false if verification failed.
+fn finish_transcript(args: &Args, transcript: &Transcript, expected: Option<&Transcript>) -> bool {
+    if let Some(path) = &args.record_io
+        && let Err(e) = std::fs::write(path, transcript.to_string())
+    {
+        eprintln!("Error writing transcript: {e}");
+    }
+
+    let Some(expected) = expected else {
+        return true;
     };
+    let expected = expected.bytes(Stream::Output);
+    let actual = transcript.bytes(Stream::Output);
+    match first_difference(&expected, &actual) {
+        None => {
+            eprintln!("Output matches the transcript ({} bytes)", actual.len());
+            true
+        }
+        Some(i) => {
+            eprintln!(
+                "Output differs from the transcript at byte {i}: expected {}, got {}",
+                describe_byte(expected.get(i)),
+                describe_byte(actual.get(i))
+            );
+            false
+        }
+    }
+}
+
+/// Returns the index of the first element that differs between `a` and `b`, counting the end of the
+/// shorter one as a difference
+fn first_difference<T: PartialEq>(a: &[T], b: &[T]) -> Option<usize> {
+    (0..a.len().max(b.len())).find(|&i| a.get(i) != b.get(i))
+}
+
+/// Describes a byte of output for error messages, where None is the end of the output
+fn describe_byte(byte: Option<&u8>) -> String {
+    match byte {
+        Some(byte) => format!("'{}'", byte.escape_ascii()),
+        None => "end of output".to_string(),
+    }
+}
+
+/// Reads the whole input selected on the command line, so it can be fed to several runs
+fn read_input(args: &Args) -> Result<Vec<u8>, String> {
+    let mut input = Vec::new();
+    input_source(args)?
+        .read_to_end(&mut input)
+        .map_err(|e| format!("Error reading input: {e}"))?;
+    Ok(input)
+}
+
+/// An interpreter fed from an in-memory input, whose output is captured in memory
+type CapturedInterpreter = Interpreter<Cursor<Vec<u8>>, Vec<u8>>;
+
+/// Returns the command line options that change what a program does, rather than how it's shown
+fn run_options(args: &Args) -> RunOptions {
+    RunOptions {
+        cell_width: args.cell_width,
+        preset_cells: args.preset_cells.clone(),
+        cell_range: args.cell_range.clone(),
+        ring_tape: args.ring_tape,
+        max_output: args.max_output,
+        output_gate: args.output_gate,
+        wide_output: args.wide_output,
+        output_endianness: args.output_endianness,
+    }
+}
+
+/// Applies the command line options that change what a program does, so every run of it has the
+/// semantics that were asked for. Fails if a cell they name is outside of the tape.
+fn configure_run<R: Read, W: Write>(
+    args: &Args,
+    interpreter: &mut Interpreter<R, W>,
+) -> Result<(), String> {
+    run_options(args)
+        .apply(interpreter)
+        .map_err(|e| e.to_string())
+}
 
+/// Runs a program to completion on the given input with the options given on the command line,
+/// capturing its output. Returns how the run ended, along with the interpreter so its final
+/// state can be inspected.
+fn run_captured(
+    args: &Args,
+    program: Program,
+    tape_size: usize,
+    fill_byte: u8,
+    eof_behaviour: EofBehaviour,
+    input: &[u8],
+) -> (Result<(), String>, CapturedInterpreter) {
     let mut interpreter = Interpreter::new(
         program,
-        args.tape_size,
-        args.eof_behaviour,
-        BufReader::new(std::io::stdin()),
+        tape_size,
+        fill_byte,
+        eof_behaviour,
+        Cursor::new(input.to_vec()),
+        Vec::new(),
     );
+    let result = configure_run(args, &mut interpreter)
+        .and_then(|_| interpreter.run().map_err(|e| e.to_string()));
+    (result, interpreter)
+}
 
-    if args.debugger {
-        let mut debugger = Debugger::new(interpreter);
-        debugger.run();
+/// Writes a minimized version of the program to `path`, after checking that it produces the same
+/// output as the original on the program's input and on no input. Returns false if it doesn't.
+fn minimize(
+    args: &Args,
+    program: Program,
+    path: &Path,
+    tape_size: usize,
+    fill_byte: u8,
+    eof_behaviour: EofBehaviour,
+) -> bool {
+    let input = match read_input(args) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    let minimized = program.minimize(fill_byte);
+    for sample in [input, Vec::new()] {
+        let (_, original) = run_captured(
+            args,
+            program.clone(),
+            tape_size,
+            fill_byte,
+            eof_behaviour,
+            &sample,
+        );
+        let (_, reduced) = run_captured(
+            args,
+            minimized.clone(),
+            tape_size,
+            fill_byte,
+            eof_behaviour,
+            &sample,
+        );
+        if let Some(i) = first_difference(original.output(), reduced.output()) {
+            eprintln!(
+                "Minimized program is not equivalent: on input \"{}\", its output differs at byte {i} ({} instead of {})",
+                sample.escape_ascii(),
+                describe_byte(reduced.output().get(i)),
+                describe_byte(original.output().get(i))
+            );
+            return false;
+        }
+    }
+
+    if let Err(e) = std::fs::write(path, minimized.to_source()) {
+        eprintln!("Error writing minimized program: {e}");
+        return false;
+    }
+    eprintln!(
+        "Minimized from {} to {} instructions",
+        program.tokens.len() - 1,
+        minimized.tokens.len() - 1
+    );
+    true
+}
+
+/// Runs the program with and without the parser's optimizations on the same input, and checks
+/// that both runs end the same way, with the same output and tape. Returns false if they diverge.
+fn verify_opt(
+    args: &Args,
+    program: Program,
+    tape_size: usize,
+    fill_byte: u8,
+    eof_behaviour: EofBehaviour,
+) -> bool {
+    let input = match read_input(args) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    let run = |program| run_captured(args, program, tape_size, fill_byte, eof_behaviour, &input);
+    let unoptimized = program.unoptimized();
+    let (optimized_result, optimized) = run(program);
+    let (unoptimized_result, unoptimized) = run(unoptimized);
+
+    let mut matches = true;
+    if optimized_result != unoptimized_result {
+        eprintln!(
+            "Runs ended differently: {optimized_result:?} with optimizations, {unoptimized_result:?} without"
+        );
+        matches = false;
+    }
+    if let Some(i) = first_difference(optimized.output(), unoptimized.output()) {
+        eprintln!(
+            "Output differs at byte {i}: {} with optimizations, {} without",
+            describe_byte(optimized.output().get(i)),
+            describe_byte(unoptimized.output().get(i))
+        );
+        matches = false;
+    }
+    if let Some(i) = first_difference(optimized.tape(), unoptimized.tape()) {
+        eprintln!(
+            "Tape differs at cell {i:#x}: {:#04x} with optimizations, {:#04x} without",
+            optimized.tape()[i],
+            unoptimized.tape()[i]
+        );
+        matches = false;
+    }
+    if optimized.ptr() != unoptimized.ptr() {
+        eprintln!(
+            "Pointer differs: {:#x} with optimizations, {:#x} without",
+            optimized.ptr(),
+            unoptimized.ptr()
+        );
+        matches = false;
+    }
+
+    if matches {
+        eprintln!(
+            "Optimized and unoptimized runs match ({} and {} steps)",
+            optimized.steps(),
+            unoptimized.steps()
+        );
+    }
+    matches
+}
+
+/// Formats the program file at `path`, printing it or overwriting the file
+fn format(args: &Args, path: &Path) {
+    let source = match parse_program(args, path) {
+        Ok(program) => program.to_formatted_source(),
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+
+    if args.in_place {
+        if let Err(e) = std::fs::write(path, source) {
+            eprintln!("Error writing formatted program: {e}");
+        }
     } else {
-        match interpreter.run() {
-            Ok(_) => (),
+        print!("{source}");
+    }
+}
+
+/// Finds the smallest tape the program runs on without moving past its end, by running it with a
+/// tape that doubles in size on every overrun, and prints its size. Returns false if the program
+/// fails for any other reason.
+fn probe_tape(args: &Args, program: Program, fill_byte: u8, eof_behaviour: EofBehaviour) -> bool {
+    /// The tape size probing starts from
+    const INITIAL_TAPE_SIZE: usize = 16;
+    /// The tape size after which the program is assumed to grow the tape forever
+    const MAX_TAPE_SIZE: usize = 1 << 24;
+
+    let input = match read_input(args) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    // The tape must hold every cell named on the command line from the start
+    let named_cells = args
+        .preset_cells
+        .iter()
+        .map(|&(address, _)| address)
+        .chain(args.output_gate);
+    let mut tape_size = named_cells.fold(INITIAL_TAPE_SIZE, |size, address| {
+        size.max((address + 1).next_power_of_two())
+    });
+    loop {
+        let (result, interpreter) = run_captured(
+            args,
+            program.clone(),
+            tape_size,
+            fill_byte,
+            eof_behaviour,
+            &input,
+        );
+        match result {
+            Ok(()) => {
+                eprintln!(
+                    "Highest pointer reached: {:#x}, in {} steps",
+                    interpreter.max_ptr(),
+                    interpreter.steps()
+                );
+                println!("{}", interpreter.max_ptr() + 1);
+                return true;
+            }
+            // Growing the tape only helps programs that move past its right end
+            Err(_)
+                if let Some(Token::Move(amount)) =
+                    interpreter.program().tokens.get(interpreter.pc())
+                    && *amount > 0
+                    && interpreter.ptr().wrapping_add(*amount as usize) >= tape_size =>
+            {
+                if tape_size >= MAX_TAPE_SIZE {
+                    eprintln!("The program still overruns a tape of {tape_size} cells, giving up");
+                    return false;
+                }
+                tape_size *= 2;
+            }
             Err(e) => {
-                eprintln!("Error running interpreter: {e}");
+                eprintln!("Error running interpreter with a tape of {tape_size} cells: {e}");
+                return false;
+            }
+        }
+    }
+}
+
+/// Checks the expectations the program declares against the interpreter's final state and the
+/// program's output, printing every one that failed. Returns false if any did, or if the program
+/// didn't halt normally.
+fn self_test<R: Read, W: Write>(
+    interpreter: &Interpreter<R, W>,
+    halted: bool,
+    output: &[u8],
+) -> bool {
+    let options = &interpreter.program().options;
+    if options.expect_ptr.is_none()
+        && options.expect_output.is_none()
+        && options.expect_number.is_none()
+    {
+        eprintln!("Warning: the program declares no expectations to self-test");
+    }
+
+    let failures = self_test_failures(interpreter, halted, output);
+    for failure in &failures {
+        eprintln!("Self-test failed: {failure}");
+    }
+    if failures.is_empty() {
+        eprintln!("Self-test passed");
+    }
+    failures.is_empty()
+}
+
+/// Lists every expectation the program declares that its run didn't meet
+fn self_test_failures<R: Read, W: Write>(
+    interpreter: &Interpreter<R, W>,
+    halted: bool,
+    output: &[u8],
+) -> Vec<String> {
+    let options = &interpreter.program().options;
+    let mut failures = Vec::new();
+    if !halted {
+        failures.push("the program stopped with an error".to_string());
+    }
+    if let Some(expected) = options.expect_ptr
+        && interpreter.ptr() != expected
+    {
+        failures.push(format!(
+            "expected the pointer to end at {expected:#x}, but it ended at {:#x}",
+            interpreter.ptr()
+        ));
+    }
+    if let Some(expected) = &options.expect_output
+        && output != expected.as_slice()
+    {
+        failures.push(format!(
+            "expected the output \"{}\", but got \"{}\"",
+            expected.escape_ascii(),
+            output.escape_ascii()
+        ));
+    }
+    if let Some(expected) = options.expect_number {
+        let actual = std::str::from_utf8(output)
+            .ok()
+            .and_then(|text| text.trim().parse::<i64>().ok());
+        match actual {
+            Some(actual) if actual == expected => (),
+            Some(actual) => failures.push(format!(
+                "expected the output to be the number {expected}, but it was {actual}"
+            )),
+            None => failures.push(format!(
+                "expected the output to be the number {expected}, but \"{}\" isn't a number",
+                output.escape_ascii()
+            )),
+        }
+    }
+    failures
+}
+
+/// Self-tests every `.bf` file in `dir`, printing a table of the results and timings. Each
+/// program runs with no input, with the options it declares. Returns false if any failed.
+fn run_dir(args: &Args, dir: &Path) -> bool {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "bf"))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error reading directory {}: {e}", dir.display());
+            return false;
+        }
+    };
+    paths.sort();
+
+    let mut failed = 0;
+    for path in &paths {
+        let started = std::time::Instant::now();
+        let failures = match parse_program(args, path) {
+            Ok(program) => {
+                let options = program.options.clone();
+                let (result, interpreter) = run_captured(
+                    args,
+                    program,
+                    args.tape_size.or(options.tape_size).unwrap_or(1024 * 64),
+                    args.fill_byte.or(options.fill_byte).unwrap_or(0),
+                    args.eof_behaviour
+                        .or(options.eof_behaviour)
+                        .unwrap_or(EofBehaviour::DontSet),
+                    &[],
+                );
+                self_test_failures(&interpreter, result.is_ok(), interpreter.output())
+            }
+            Err(e) => vec![e],
+        };
+        let elapsed = started.elapsed();
+
+        let status = if failures.is_empty() { "PASS" } else { "FAIL" };
+        println!(
+            "{status}  {:>9.3}s  {}",
+            elapsed.as_secs_f64(),
+            path.display()
+        );
+        for failure in &failures {
+            println!("      {failure}");
+        }
+        if !failures.is_empty() {
+            failed += 1;
+        }
+    }
+    println!("{} passed, {failed} failed", paths.len() - failed);
+    failed == 0
+}
+
+/// Writes the input the program read to the file given with `--save-input`, if any
+fn save_input<R: Read, W: Write>(args: &Args, interpreter: &Interpreter<R, W>) {
+    if let Some(path) = &args.save_input
+        && let Some(input) = interpreter.consumed_input()
+        && let Err(e) = std::fs::write(path, input)
+    {
+        eprintln!("Error saving input: {e}");
+    }
+}
+
+/// Prints the reports requested on the command line after the program has stopped
+fn report<R: Read, W: Write>(args: &Args, interpreter: &Interpreter<R, W>) {
+    if args.report_max_ptr {
+        eprintln!("Max pointer: {:#x}", interpreter.max_ptr());
+    }
+    if args.report_travel {
+        eprintln!(
+            "Pointer travel: {} cells in {} steps",
+            interpreter.travel(),
+            interpreter.steps()
+        );
+    }
+    if args.tape_summary {
+        interpreter.print_tape_summary();
+    }
+    if args.opcode_stats {
+        interpreter.print_opcode_stats();
+    }
+    if args.hash_tape_on_exit {
+        eprintln!("Tape hash: {:016x}", interpreter.tape_hash());
+    }
+    if let Some(path) = &args.profile_folded {
+        // Each line is a stack of frames separated by semicolons, followed by a sample count
+        let mut folded = String::new();
+        for (loops, steps) in interpreter.loop_profile() {
+            folded.push_str("program");
+            for address in loops {
+                folded.push_str(&format!(";[@{address:#x}"));
+            }
+            folded.push_str(&format!(" {steps}\n"));
+        }
+        if let Err(e) = std::fs::write(path, folded) {
+            eprintln!("Error writing profile: {e}");
+        }
+    }
+}
+
+/// A xorshift64* random number generator, so fuzzing sessions can be reproduced from their seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // The state must never be zero
+        Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+}
+
+/// How a fuzzing run failed
+#[derive(Debug, Clone, PartialEq)]
+enum FuzzFailure {
+    Error(String),
+    StepLimit,
+    Panic(String),
+}
+
+impl std::fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuzzFailure::Error(e) => write!(f, "error: {e}"),
+            FuzzFailure::StepLimit => write!(f, "didn't halt within the step limit"),
+            FuzzFailure::Panic(message) => write!(f, "panic: {message}"),
+        }
+    }
+}
+
+/// Runs the program on random inputs, reporting each failing run with a shrunk input that still
+/// fails the same way. Returns false if any run failed.
+fn fuzz(
+    args: &Args,
+    program: Program,
+    runs: u64,
+    tape_size: usize,
+    fill_byte: u8,
+    eof_behaviour: EofBehaviour,
+) -> bool {
+    /// The longest input generated
+    const MAX_INPUT_LENGTH: u64 = 64;
+
+    let run = |input: &[u8]| {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut interpreter = Interpreter::new(
+                program.clone(),
+                tape_size,
+                fill_byte,
+                eof_behaviour,
+                Cursor::new(input.to_vec()),
+                std::io::sink(),
+            );
+            configure_run(args, &mut interpreter)?;
+            interpreter
+                .cont_for(args.fuzz_steps)
+                .map_err(|e| e.to_string())
+        }));
+        match result {
+            Ok(Ok(Some(_))) => None,
+            Ok(Ok(None)) => Some(FuzzFailure::StepLimit),
+            Ok(Err(e)) => Some(FuzzFailure::Error(e)),
+            Err(payload) => Some(FuzzFailure::Panic(
+                payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default(),
+            )),
+        }
+    };
+
+    // Panics are reported along with their input, instead of through the default hook
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut rng = Rng::new(args.fuzz_seed);
+    let mut failures = 0;
+    for index in 0..runs {
+        let length = rng.next() % (MAX_INPUT_LENGTH + 1);
+        let input: Vec<u8> = (0..length).map(|_| rng.next() as u8).collect();
+        let Some(failure) = run(&input) else {
+            continue;
+        };
+        failures += 1;
+        let shrunk = shrink_input(input.clone(), |input| run(input).as_ref() == Some(&failure));
+        eprintln!("Run {index} failed: {failure}");
+        eprintln!("  input: \"{}\"", input.escape_ascii());
+        eprintln!("  smallest failing input: \"{}\"", shrunk.escape_ascii());
+    }
+    let _ = std::panic::take_hook();
+
+    eprintln!("{failures} of {runs} runs failed (seed {})", args.fuzz_seed);
+    failures == 0
+}
+
+/// Shrinks an input while `fails` still holds for it, by removing bytes and then lowering the
+/// remaining ones towards zero
+fn shrink_input(mut input: Vec<u8>, fails: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut index = 0;
+    while index < input.len() {
+        let mut candidate = input.clone();
+        candidate.remove(index);
+        if fails(&candidate) {
+            input = candidate;
+        } else {
+            index += 1;
+        }
+    }
+    for index in 0..input.len() {
+        for value in 0..input[index] {
+            let mut candidate = input.clone();
+            candidate[index] = value;
+            if fails(&candidate) {
+                input = candidate;
+                break;
             }
         }
     }
+    input
 }
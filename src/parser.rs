@@ -1,20 +1,78 @@
+pub mod ast;
+mod compiled;
+pub mod dialect;
+mod disasm;
+mod graph;
+pub mod identify;
+pub mod loops;
+mod minimize;
+mod source;
+mod units;
+
+use crate::interpreter::transcript::unescape;
+use crate::interpreter::{CellWidth, EofBehaviour};
+use crate::parser::dialect::Dialect;
+use clap::ValueEnum;
+use std::borrow::Cow;
 use std::fmt::Display;
 use std::io::{BufRead, BufReader, Read};
 use thiserror::Error;
 
+/// The character UTF-8 files are sometimes prefixed with, which is skipped before parsing
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
 #[derive(Error, Debug)]
 pub enum ParserError {
-    #[error("] has no matching [ ")]
-    MissingOpen,
-    #[error("[ has no matching ]")]
-    MissingClose,
+    /// `last_balanced` is the `]` after which the brackets were last balanced, before the stray
+    /// `]` made the depth negative, if any loop was closed before it
+    #[error("] at {position} has no matching [{}", describe_balanced(.last_balanced))]
+    MissingOpen {
+        position: SourcePosition,
+        last_balanced: Option<SourcePosition>,
+    },
+    /// Holds the positions of every `[` left open at the end of the program, outermost first, so
+    /// the depth remaining at the end is its length
+    #[error("[ at {} has no matching ] (depth {} at the end of the program)", .open[0], .open.len())]
+    MissingClose { open: Vec<SourcePosition> },
+    #[error("line {line}: malformed directive `{text}`")]
+    BadDirective { line: usize, text: String },
+    #[error("line {line}: invalid dialect mapping `{text}`")]
+    BadMapping { line: usize, text: String },
+    #[error("Invalid compiled program: {0}")]
+    InvalidCompiled(String),
     #[error("IO Error")]
     IOError(#[from] std::io::Error),
 }
 
+/// A position in a program's source. Columns are unknown for programs written in a dialect, as
+/// their commands don't map to single characters.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+fn describe_balanced(last_balanced: &Option<SourcePosition>) -> String {
+    match last_balanced {
+        Some(position) => format!(" (brackets were last balanced after the ] at {position})"),
+        None => String::new(),
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {column}", self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Token {
-    Increment(u8),
+    /// Adds a signed amount to the current cell. Runs of `+` and `-` are folded into one, which
+    /// wraps at 32 bits, so it adds the same as the run would for any cell width.
+    Increment(i32),
     Move(isize),
     JumpZero(usize),
     JumpNotZero(usize),
@@ -28,11 +86,10 @@ impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Increment(value) => {
-                let value = *value as i8;
-                if value > 0 {
-                    write!(f, "+{}", value as u8)
+                if *value > 0 {
+                    write!(f, "+{value}")
                 } else {
-                    write!(f, "-{}", -value as u8)
+                    write!(f, "-{}", value.unsigned_abs())
                 }
             }
             Self::Move(value) => {
@@ -62,32 +119,114 @@ impl Display for Token {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Unit {
     pub description: String,
     pub start: usize,
     pub end: usize,
 }
 
-#[derive(Debug)]
+/// Runtime options a program declares for itself through `;!` directives
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProgramOptions {
+    pub tape_size: Option<usize>,
+    pub fill_byte: Option<u8>,
+    pub eof_behaviour: Option<EofBehaviour>,
+    pub cell_width: Option<CellWidth>,
+    /// Where the pointer should be once the program halts, checked by `--self-test`
+    pub expect_ptr: Option<usize>,
+    /// The output the program should produce, checked by `--self-test`
+    pub expect_output: Option<Vec<u8>>,
+    /// The number the program's output should read as in decimal, ignoring surrounding
+    /// whitespace, checked by `--self-test`
+    pub expect_number: Option<i64>,
+}
+
+/// Optional warnings about suspicious code the parser can emit
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Lints {
+    /// Warn about runs of increments or moves that partly cancel out, like `+-` or `<>`
+    pub noops: bool,
+    /// Warn about loops whose body moves the pointer by a nonzero amount on every iteration
+    pub drift: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct Program {
     pub units: Vec<Unit>,
     pub tokens: Vec<Token>,
+    /// Where each token starts in the program's source. Empty if the program wasn't parsed from
+    /// source, and possibly shorter than `tokens` if code was added afterwards.
+    pub positions: Vec<SourcePosition>,
+    pub options: ProgramOptions,
+    pub warnings: Vec<String>,
 }
 
 impl Program {
-    pub fn parse<T: Read>(input: BufReader<T>, parse_print: bool) -> Result<Program, ParserError> {
+    /// Parses a program. If a dialect is given, the program's source is translated from it into
+    /// brainfuck before being parsed. The enabled lints are reported as warnings.
+    pub fn parse<T: Read>(
+        input: BufReader<T>,
+        parse_print: bool,
+        lints: Lints,
+        dialect: Option<&Dialect>,
+    ) -> Result<Program, ParserError> {
         let mut tokens = Vec::new();
+        let mut positions = Vec::new();
+        let mut lines = 0;
         let mut next_token = None;
+        // Where the run of increments or moves being coalesced into `next_token` started, and the
+        // last command in it
+        let mut run_start = None;
+        let mut run_last = ' ';
         let mut jump_stack = Vec::new();
+        // The last `]` that closed an outermost loop, for reporting a stray `]`
+        let mut last_balanced = None;
         let mut units: Vec<Unit> = Vec::new();
+        let mut options = ProgramOptions::default();
+        let mut warnings = Vec::new();
+        let mut dialect_carry = String::new();
 
-        for line in input.lines() {
-            let line = line?;
+        for (line_number, line) in input.lines().enumerate() {
+            let mut line = line?;
+            lines = line_number + 1;
 
+            // Editors on Windows often start UTF-8 files with a byte order mark, which would hide a
+            // shebang or a unit name on the first line
+            if line_number == 0 && line.starts_with(BYTE_ORDER_MARK) {
+                line.drain(..BYTE_ORDER_MARK.len_utf8());
+            }
+
+            // A shebang (e.g. `#!/usr/bin/env brainstorm`) on the first line makes the file
+            // executable, and would otherwise be parsed as a print and a move
+            if line_number == 0 && line.starts_with("#!") {
+                continue;
+            }
+
+            let indentation = line.chars().take_while(|c| c.is_whitespace()).count();
             let line = line.trim();
+            if let Some(directive) = line.strip_prefix(";!") {
+                Self::parse_directive(&mut options, &mut warnings, line_number + 1, directive)?;
+                continue;
+            }
+
+            // Expectations are written as `;;expect:`, to read like a comment
+            let bad_expectation = || ParserError::BadDirective {
+                line: line_number + 1,
+                text: line.to_string(),
+            };
+            if let Some(expected) = line.strip_prefix(";;expect:") {
+                options.expect_output = Some(unescape(expected).ok_or_else(bad_expectation)?);
+                continue;
+            }
+            if let Some(expected) = line.strip_prefix(";;expect-number:") {
+                options.expect_number =
+                    Some(expected.trim().parse().map_err(|_| bad_expectation())?);
+                continue;
+            }
+
             if let Some(line) = line.strip_prefix(";") {
-                Self::push_token(&mut tokens, &mut next_token);
+                Self::push_token(&mut tokens, &mut positions, &mut next_token, run_start);
 
                 if units.is_empty() && !tokens.is_empty() {
                     units.push(Unit {
@@ -108,21 +247,40 @@ impl Program {
                 });
             }
 
-            for char in line.chars() {
+            let line = match dialect {
+                Some(dialect) => Cow::Owned(dialect.translate(line, &mut dialect_carry)),
+                None => Cow::Borrowed(line),
+            };
+
+            for (column, char) in line.chars().enumerate() {
+                let position = SourcePosition {
+                    line: line_number + 1,
+                    column: dialect.is_none().then_some(indentation + column + 1),
+                };
                 match char {
                     '+' | '-' => {
-                        let initial_value = if char == '+' { 1 } else { 255 };
+                        let initial_value = if char == '+' { 1 } else { -1 };
 
                         match next_token {
                             Some(Token::Increment(value)) => {
                                 next_token =
                                     Some(Token::Increment(value.wrapping_add(initial_value)));
+                                if lints.noops && run_last != char {
+                                    Self::warn_noop(&mut warnings, run_start, "`+` and `-`");
+                                }
                             }
                             _ => {
-                                Self::push_token(&mut tokens, &mut next_token);
+                                Self::push_token(
+                                    &mut tokens,
+                                    &mut positions,
+                                    &mut next_token,
+                                    run_start,
+                                );
                                 next_token = Some(Token::Increment(initial_value));
+                                run_start = Some(position);
                             }
                         }
+                        run_last = char;
                     }
                     '>' | '<' => {
                         let initial_value = if char == '>' { 1 } else { -1 };
@@ -130,38 +288,67 @@ impl Program {
                         match next_token {
                             Some(Token::Move(value)) => {
                                 next_token = Some(Token::Move(value.wrapping_add(initial_value)));
+                                if lints.noops && run_last != char {
+                                    Self::warn_noop(&mut warnings, run_start, "`>` and `<`");
+                                }
                             }
                             _ => {
-                                Self::push_token(&mut tokens, &mut next_token);
+                                Self::push_token(
+                                    &mut tokens,
+                                    &mut positions,
+                                    &mut next_token,
+                                    run_start,
+                                );
                                 next_token = Some(Token::Move(initial_value));
+                                run_start = Some(position);
                             }
                         }
+                        run_last = char;
                     }
                     '.' => {
-                        Self::push_token(&mut tokens, &mut next_token);
+                        Self::push_token(&mut tokens, &mut positions, &mut next_token, run_start);
                         tokens.push(Token::Output);
+                        positions.push(position);
                     }
                     ',' => {
-                        Self::push_token(&mut tokens, &mut next_token);
+                        Self::push_token(&mut tokens, &mut positions, &mut next_token, run_start);
                         next_token = Some(Token::Input);
+                        run_start = Some(position);
                     }
                     '[' => {
-                        Self::push_token(&mut tokens, &mut next_token);
+                        Self::push_token(&mut tokens, &mut positions, &mut next_token, run_start);
                         tokens.push(Token::JumpZero(0)); // Value is set when the matching ']' is found
-                        jump_stack.push(tokens.len());
+                        positions.push(position);
+                        jump_stack.push((tokens.len(), position));
                     }
                     ']' => {
-                        Self::push_token(&mut tokens, &mut next_token);
-                        let start = jump_stack.pop().ok_or(ParserError::MissingOpen)?;
+                        Self::push_token(&mut tokens, &mut positions, &mut next_token, run_start);
+                        let (start, open) = jump_stack.pop().ok_or(ParserError::MissingOpen {
+                            position,
+                            last_balanced,
+                        })?;
+                        if jump_stack.is_empty() {
+                            last_balanced = Some(position);
+                        }
+                        if lints.drift
+                            && let Some(drift) = Self::loop_drift(&tokens[start..])
+                            && drift != 0
+                        {
+                            warnings.push(format!(
+                                "{open}: loop moves the pointer by {drift} on every iteration"
+                            ));
+                        }
                         tokens[start - 1] = Token::JumpZero(tokens.len() + 1);
                         tokens.push(Token::JumpNotZero(start));
+                        positions.push(position);
                     }
                     '#' => {
                         if !parse_print {
                             continue;
                         }
-                        Self::push_token(&mut tokens, &mut next_token);
+                        Self::push_token(&mut tokens, &mut positions, &mut next_token, run_start);
                         tokens.push(Token::PrintState);
+                        positions.push(position);
                     }
                     _ => continue,
                 }
@@ -170,11 +357,21 @@ impl Program {
 
         if let Some(token) = next_token.take() {
             tokens.push(token);
+            positions.extend(run_start);
         }
         tokens.push(Token::Eof);
+        positions.push(SourcePosition {
+            line: lines + 1,
+            column: None,
+        });
 
         if !jump_stack.is_empty() {
-            return Err(ParserError::MissingClose);
+            return Err(ParserError::MissingClose {
+                open: jump_stack
+                    .into_iter()
+                    .map(|(_, position)| position)
+                    .collect(),
+            });
         }
 
         // If no units, create a default unit
@@ -189,15 +386,370 @@ impl Program {
         // Update last unit
         units.last_mut().unwrap().end = tokens.len();
 
-        Ok(Program { units, tokens })
+        Ok(Program {
+            units,
+            tokens,
+            positions,
+            options,
+            warnings,
+        })
     }
 
-    fn push_token(tokens: &mut Vec<Token>, token: &mut Option<Token>) {
+    /// Finds a unit by its description, ignoring case. Exact matches are preferred, otherwise the
+    /// first unit whose description contains `name` is returned.
+    pub fn find_unit(&self, name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        let descriptions: Vec<_> = self
+            .units
+            .iter()
+            .map(|unit| unit.description.to_lowercase())
+            .collect();
+        descriptions
+            .iter()
+            .position(|description| *description == name)
+            .or_else(|| {
+                descriptions
+                    .iter()
+                    .position(|description| description.contains(&name))
+            })
+    }
+
+    /// Appends another program's code to the end of this one, just before the EOF. Jump targets
+    /// are shifted to their new addresses, and the code becomes part of the last unit.
+    pub fn append(&mut self, other: &Program) {
+        let offset = self.tokens.len() - 1;
+        self.tokens.pop();
+        self.positions.truncate(offset);
+        self.tokens
+            .extend(other.tokens.iter().map(|token| match *token {
+                Token::JumpZero(target) => Token::JumpZero(target + offset),
+                Token::JumpNotZero(target) => Token::JumpNotZero(target + offset),
+                token => token,
+            }));
+        self.units.last_mut().unwrap().end = self.tokens.len();
+    }
+
+    /// Returns an equivalent program without the parser's optimizations, in which every increment
+    /// and move is split into single steps. Jump targets and units are remapped accordingly.
+    pub fn unoptimized(&self) -> Program {
+        // The address every original token starts at in the new program, plus the end address
+        let mut addresses = Vec::with_capacity(self.tokens.len() + 1);
+        let mut tokens = Vec::new();
+        let mut positions = Vec::new();
+        for (address, token) in self.tokens.iter().enumerate() {
+            addresses.push(tokens.len());
+            match *token {
+                Token::Increment(value) => tokens
+                    .extend((0..value.unsigned_abs()).map(|_| Token::Increment(value.signum()))),
+                Token::Move(value) => {
+                    tokens.extend((0..value.unsigned_abs()).map(|_| Token::Move(value.signum())))
+                }
+                token => tokens.push(token),
+            }
+            if let Some(&position) = self.positions.get(address) {
+                positions.resize(tokens.len(), position);
+            }
+        }
+        addresses.push(tokens.len());
+
+        for token in &mut tokens {
+            match token {
+                Token::JumpZero(target) | Token::JumpNotZero(target) => {
+                    *target = addresses[*target - 1] + 1
+                }
+                _ => (),
+            }
+        }
+
+        Program {
+            units: self
+                .units
+                .iter()
+                .map(|unit| Unit {
+                    description: unit.description.clone(),
+                    start: addresses[unit.start],
+                    end: addresses[unit.end],
+                })
+                .collect(),
+            tokens,
+            positions,
+            options: self.options.clone(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Parses a single `;!name value` directive into `options`. Unknown directives are ignored
+    /// with a warning, while known directives with a missing or invalid value are an error.
+    fn parse_directive(
+        options: &mut ProgramOptions,
+        warnings: &mut Vec<String>,
+        line: usize,
+        directive: &str,
+    ) -> Result<(), ParserError> {
+        let bad_directive = || ParserError::BadDirective {
+            line,
+            text: format!(";!{directive}"),
+        };
+
+        let mut parts = directive.split_whitespace();
+        let name = parts.next().ok_or_else(bad_directive)?;
+        let value = parts.next();
+        if parts.next().is_some() {
+            return Err(bad_directive());
+        }
+
+        match name {
+            "tape-size" => {
+                options.tape_size = Some(
+                    value
+                        .and_then(|value| value.parse().ok())
+                        .filter(|&size| size > 0)
+                        .ok_or_else(bad_directive)?,
+                );
+            }
+            "fill-byte" => {
+                options.fill_byte = Some(
+                    value
+                        .and_then(|value| {
+                            u8::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+                        })
+                        .ok_or_else(bad_directive)?,
+                );
+            }
+            "eof" => {
+                options.eof_behaviour = Some(
+                    value
+                        .and_then(|value| EofBehaviour::from_str(value, true).ok())
+                        .ok_or_else(bad_directive)?,
+                );
+            }
+            "cell-width" => {
+                options.cell_width = Some(
+                    value
+                        .and_then(|value| CellWidth::from_str(value, false).ok())
+                        .ok_or_else(bad_directive)?,
+                );
+            }
+            "expect-ptr" => {
+                options.expect_ptr = Some(
+                    value
+                        .and_then(|value| {
+                            usize::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+                        })
+                        .ok_or_else(bad_directive)?,
+                );
+            }
+            _ => warnings.push(format!("line {line}: unknown directive `;!{name}` ignored")),
+        }
+        Ok(())
+    }
+
+    /// Returns the net pointer movement of a loop body, or None if it's unknown because a nested
+    /// loop drifts itself
+    fn loop_drift(body: &[Token]) -> Option<isize> {
+        // The movement of the body and of every nested loop being scanned, innermost last
+        let mut drifts = vec![0isize];
+        let mut known = true;
+        for token in body {
+            match token {
+                Token::JumpZero(_) => drifts.push(0),
+                Token::JumpNotZero(_) => known &= drifts.pop() == Some(0),
+                Token::Move(value) => {
+                    let drift = drifts.last_mut().unwrap();
+                    *drift = drift.wrapping_add(*value);
+                }
+                _ => (),
+            }
+        }
+        known.then_some(drifts[0])
+    }
+
+    /// Warns about a run of increments or moves starting at `start` in which `commands` partly
+    /// cancel each other out, unless that run was already warned about
+    fn warn_noop(warnings: &mut Vec<String>, start: Option<SourcePosition>, commands: &str) {
+        let Some(start) = start else {
+            return;
+        };
+        let warning = format!("{start}: {commands} cancel each other out");
+        if warnings.last() != Some(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    /// Pushes the pending token, which started at `start`, unless it does nothing
+    fn push_token(
+        tokens: &mut Vec<Token>,
+        positions: &mut Vec<SourcePosition>,
+        token: &mut Option<Token>,
+        start: Option<SourcePosition>,
+    ) {
         if let Some(token) = token.take() {
             match token {
                 Token::Increment(0) | Token::Move(0) => (),
-                _ => tokens.push(token),
+                _ => {
+                    tokens.push(token);
+                    positions.extend(start);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Program, ParserError> {
+        Program::parse(
+            BufReader::new(source.as_bytes()),
+            false,
+            Lints::default(),
+            None,
+        )
+    }
+
+    fn options(source: &str) -> ProgramOptions {
+        parse(source).unwrap().options
+    }
+
+    /// Asserts that `directive` is reported as malformed on the line it's on
+    fn assert_malformed(directive: &str) {
+        match parse(&format!("+\n{directive}\n")) {
+            Err(ParserError::BadDirective { line, text }) => {
+                assert_eq!(line, 2);
+                assert_eq!(text, directive);
             }
+            other => panic!("expected `{directive}` to be malformed, got {other:?}"),
         }
     }
+
+    #[test]
+    fn tape_size_directive() {
+        assert_eq!(options(";!tape-size 32768").tape_size, Some(32768));
+        assert_malformed(";!tape-size");
+        assert_malformed(";!tape-size 0");
+        assert_malformed(";!tape-size big");
+        assert_malformed(";!tape-size 10 20");
+    }
+
+    #[test]
+    fn fill_byte_directive() {
+        assert_eq!(options(";!fill-byte 0xAA").fill_byte, Some(0xaa));
+        assert_eq!(options(";!fill-byte 7f").fill_byte, Some(0x7f));
+        assert_malformed(";!fill-byte");
+        assert_malformed(";!fill-byte 0x100");
+        assert_malformed(";!fill-byte zz");
+    }
+
+    #[test]
+    fn eof_directive() {
+        assert_eq!(
+            options(";!eof set-zero").eof_behaviour,
+            Some(EofBehaviour::SetZero)
+        );
+        assert_eq!(
+            options(";!eof SET-MINUS-ONE").eof_behaviour,
+            Some(EofBehaviour::SetMinusOne)
+        );
+        assert_eq!(
+            options(";!eof dont-set").eof_behaviour,
+            Some(EofBehaviour::DontSet)
+        );
+        assert_malformed(";!eof");
+        assert_malformed(";!eof set-one");
+    }
+
+    #[test]
+    fn expect_ptr_directive() {
+        assert_eq!(options(";!expect-ptr 0x40").expect_ptr, Some(0x40));
+        assert_eq!(options(";!expect-ptr 0").expect_ptr, Some(0));
+        assert_malformed(";!expect-ptr");
+        assert_malformed(";!expect-ptr -1");
+        assert_malformed(";!expect-ptr here");
+    }
+
+    #[test]
+    fn cell_width_directive() {
+        assert_eq!(
+            options(";!cell-width 16").cell_width,
+            Some(CellWidth::Bits16)
+        );
+        assert_malformed(";!cell-width");
+        assert_malformed(";!cell-width 12");
+    }
+
+    #[test]
+    fn empty_directive_is_malformed() {
+        assert_malformed(";!");
+    }
+
+    #[test]
+    fn unknown_directive_is_ignored_with_a_warning() {
+        let program = parse(";!tape-size 64\n;!cell-height 3\n+\n").unwrap();
+        assert_eq!(program.options.tape_size, Some(64));
+        assert_eq!(
+            program.warnings,
+            ["line 2: unknown directive `;!cell-height` ignored"]
+        );
+        assert_eq!(program.tokens, [Token::Increment(1), Token::Eof]);
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let program = parse("\u{feff}; first\n+.\n").unwrap();
+        assert_eq!(program.units[0].description, "first");
+        assert_eq!(
+            program.tokens,
+            [Token::Increment(1), Token::Output, Token::Eof]
+        );
+        // Columns are counted after the mark
+        assert_eq!(
+            program.positions[0],
+            SourcePosition {
+                line: 2,
+                column: Some(1)
+            }
+        );
+
+        let program = parse("\u{feff}+.").unwrap();
+        assert_eq!(
+            program.tokens,
+            [Token::Increment(1), Token::Output, Token::Eof]
+        );
+        assert_eq!(program.positions[0].column, Some(1));
+    }
+
+    #[test]
+    fn byte_order_mark_before_a_shebang_is_skipped() {
+        let program = parse("\u{feff}#!/usr/bin/env -S brainstorm -p\n+>-\n").unwrap();
+        assert_eq!(
+            program.tokens,
+            [
+                Token::Increment(1),
+                Token::Move(1),
+                Token::Increment(-1),
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_order_mark_elsewhere_is_ignored_like_a_comment() {
+        let with_mark = Program::parse(
+            BufReader::new("+\n\u{feff}#>".as_bytes()),
+            true,
+            Lints::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            with_mark.tokens,
+            [
+                Token::Increment(1),
+                Token::PrintState,
+                Token::Move(1),
+                Token::Eof
+            ]
+        );
+    }
 }
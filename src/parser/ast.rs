@@ -0,0 +1,87 @@
+use crate::parser::{Program, ProgramOptions, Token, Unit};
+
+/// A node of a program's tree representation, in which loops contain their bodies rather than
+/// jump targets
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Any token other than a jump or EOF
+    Op(Token),
+    /// A loop and its body
+    Loop(Vec<Node>),
+}
+
+impl Program {
+    /// Reconstructs the program's loop structure as a tree. The trailing EOF is omitted.
+    pub fn to_ast(&self) -> Vec<Node> {
+        Self::tokens_to_ast(&self.tokens[..self.tokens.len() - 1])
+    }
+
+    fn tokens_to_ast(tokens: &[Token]) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                Token::JumpZero(_) => {
+                    // Jump targets are absolute, so find the matching ] by depth instead, which
+                    // also works on slices of the program
+                    let mut depth = 0;
+                    let mut end = i;
+                    for (j, token) in tokens.iter().enumerate().skip(i) {
+                        match token {
+                            Token::JumpZero(_) => depth += 1,
+                            Token::JumpNotZero(_) => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 {
+                            end = j;
+                            break;
+                        }
+                    }
+                    nodes.push(Node::Loop(Self::tokens_to_ast(&tokens[i + 1..end])));
+                    i = end + 1;
+                }
+                Token::JumpNotZero(_) | Token::Eof => i += 1,
+                token => {
+                    nodes.push(Node::Op(token));
+                    i += 1;
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Flattens a tree back into a program, recomputing jump targets. The program has a single
+    /// unit and no options.
+    pub fn from_ast(ast: &[Node]) -> Program {
+        let mut tokens = Vec::new();
+        Self::flatten(ast, &mut tokens);
+        tokens.push(Token::Eof);
+
+        Program {
+            units: vec![Unit {
+                description: "No Unit Information".to_string(),
+                start: 0,
+                end: tokens.len(),
+            }],
+            tokens,
+            positions: Vec::new(),
+            options: ProgramOptions::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn flatten(ast: &[Node], tokens: &mut Vec<Token>) {
+        for node in ast {
+            match node {
+                Node::Op(token) => tokens.push(*token),
+                Node::Loop(body) => {
+                    let start = tokens.len();
+                    tokens.push(Token::JumpZero(0)); // Value is set once the body is flattened
+                    Self::flatten(body, tokens);
+                    tokens[start] = Token::JumpZero(tokens.len() + 1);
+                    tokens.push(Token::JumpNotZero(start + 1));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use crate::interpreter::{CellWidth, EofBehaviour};
+use crate::parser::{ParserError, Program, ProgramOptions, Token, Unit};
+
+/// Magic bytes at the start of every compiled program
+const MAGIC: &[u8; 4] = b"BSTM";
+/// Version of the compiled format. Must be bumped whenever the format changes, so that programs
+/// compiled with an older version are rejected rather than misread.
+const VERSION: u8 = 6;
+
+/// Reads values from a compiled program, failing if the data ends early
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ParserError> {
+        if self.bytes.len() < N {
+            return Err(invalid("unexpected end of data"));
+        }
+        let (value, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(value.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, ParserError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, ParserError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ParserError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn usize(&mut self) -> Result<usize, ParserError> {
+        usize::try_from(self.u64()?).map_err(|_| invalid("value too large for this platform"))
+    }
+
+    fn isize(&mut self) -> Result<isize, ParserError> {
+        isize::try_from(i64::from_le_bytes(self.take()?))
+            .map_err(|_| invalid("value too large for this platform"))
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, ParserError>,
+    ) -> Result<Option<T>, ParserError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(invalid("invalid option tag")),
+        }
+    }
+}
+
+fn invalid(reason: &str) -> ParserError {
+    ParserError::InvalidCompiled(reason.to_string())
+}
+
+fn push_usize(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u64).to_le_bytes());
+}
+
+fn push_option<T>(bytes: &mut Vec<u8>, value: Option<T>, push: impl FnOnce(&mut Vec<u8>, T)) {
+    match value {
+        Some(value) => {
+            bytes.push(1);
+            push(bytes, value);
+        }
+        None => bytes.push(0),
+    }
+}
+
+impl Program {
+    /// Serializes the program (its tokens, units and options) to a compact binary format, which
+    /// can be loaded back with `Program::from_bytes` without parsing the source again
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+
+        push_option(&mut bytes, self.options.tape_size, push_usize);
+        push_option(&mut bytes, self.options.fill_byte, |bytes, fill_byte| {
+            bytes.push(fill_byte)
+        });
+        push_option(
+            &mut bytes,
+            self.options.eof_behaviour,
+            |bytes, eof| match eof {
+                EofBehaviour::SetZero => bytes.push(0),
+                EofBehaviour::SetMinusOne => bytes.push(1),
+                EofBehaviour::DontSet => bytes.push(2),
+                EofBehaviour::SetValue(value) => bytes.extend_from_slice(&[3, value]),
+            },
+        );
+        push_option(&mut bytes, self.options.cell_width, |bytes, cell_width| {
+            bytes.push(cell_width.bits() as u8)
+        });
+        push_option(&mut bytes, self.options.expect_ptr, push_usize);
+        push_option(
+            &mut bytes,
+            self.options.expect_output.as_ref(),
+            |bytes, output| {
+                push_usize(bytes, output.len());
+                bytes.extend_from_slice(output);
+            },
+        );
+        push_option(&mut bytes, self.options.expect_number, |bytes, number| {
+            bytes.extend_from_slice(&number.to_le_bytes())
+        });
+
+        push_usize(&mut bytes, self.units.len());
+        for unit in &self.units {
+            push_usize(&mut bytes, unit.description.len());
+            bytes.extend_from_slice(unit.description.as_bytes());
+            push_usize(&mut bytes, unit.start);
+            push_usize(&mut bytes, unit.end);
+        }
+
+        push_usize(&mut bytes, self.tokens.len());
+        for token in &self.tokens {
+            match *token {
+                Token::Increment(value) => {
+                    bytes.push(0);
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                Token::Move(value) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&(value as i64).to_le_bytes());
+                }
+                Token::JumpZero(target) => {
+                    bytes.push(2);
+                    push_usize(&mut bytes, target);
+                }
+                Token::JumpNotZero(target) => {
+                    bytes.push(3);
+                    push_usize(&mut bytes, target);
+                }
+                Token::Input => bytes.push(4),
+                Token::Output => bytes.push(5),
+                Token::PrintState => bytes.push(6),
+                Token::Eof => bytes.push(7),
+            }
+        }
+
+        bytes
+    }
+
+    /// Loads a program serialized with `Program::to_bytes`. Fails if the data isn't a compiled
+    /// program, was compiled by an incompatible version, or doesn't describe a valid program.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, ParserError> {
+        let mut reader = ByteReader { bytes };
+        if &reader.take::<4>()? != MAGIC {
+            return Err(invalid("not a compiled brainstorm program"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(ParserError::InvalidCompiled(format!(
+                "unsupported version {version}, expected {VERSION}"
+            )));
+        }
+
+        let options = ProgramOptions {
+            tape_size: reader.option(ByteReader::usize)?,
+            fill_byte: reader.option(ByteReader::u8)?,
+            eof_behaviour: reader.option(|reader| match reader.u8()? {
+                0 => Ok(EofBehaviour::SetZero),
+                1 => Ok(EofBehaviour::SetMinusOne),
+                2 => Ok(EofBehaviour::DontSet),
+                3 => Ok(EofBehaviour::SetValue(reader.u8()?)),
+                _ => Err(invalid("invalid eof behaviour")),
+            })?,
+            cell_width: reader.option(|reader| match reader.u8()? {
+                8 => Ok(CellWidth::Bits8),
+                16 => Ok(CellWidth::Bits16),
+                32 => Ok(CellWidth::Bits32),
+                _ => Err(invalid("invalid cell width")),
+            })?,
+            expect_ptr: reader.option(ByteReader::usize)?,
+            expect_output: reader.option(|reader| {
+                let length = reader.usize()?;
+                if reader.bytes.len() < length {
+                    return Err(invalid("unexpected end of data"));
+                }
+                let (output, rest) = reader.bytes.split_at(length);
+                reader.bytes = rest;
+                Ok(output.to_vec())
+            })?,
+            expect_number: reader.option(|reader| Ok(i64::from_le_bytes(reader.take()?)))?,
+        };
+
+        let unit_count = reader.usize()?;
+        let mut units = Vec::new();
+        for _ in 0..unit_count {
+            let length = reader.usize()?;
+            if reader.bytes.len() < length {
+                return Err(invalid("unexpected end of data"));
+            }
+            let (description, rest) = reader.bytes.split_at(length);
+            reader.bytes = rest;
+            units.push(Unit {
+                description: String::from_utf8(description.to_vec())
+                    .map_err(|_| invalid("unit description is not valid UTF-8"))?,
+                start: reader.usize()?,
+                end: reader.usize()?,
+            });
+        }
+
+        let token_count = reader.usize()?;
+        let mut tokens = Vec::new();
+        for _ in 0..token_count {
+            tokens.push(match reader.u8()? {
+                0 => Token::Increment(reader.i32()?),
+                1 => Token::Move(reader.isize()?),
+                2 => Token::JumpZero(reader.usize()?),
+                3 => Token::JumpNotZero(reader.usize()?),
+                4 => Token::Input,
+                5 => Token::Output,
+                6 => Token::PrintState,
+                7 => Token::Eof,
+                _ => return Err(invalid("invalid token")),
+            });
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(invalid("trailing data after program"));
+        }
+        if tokens.last() != Some(&Token::Eof) {
+            return Err(invalid("program does not end with EOF"));
+        }
+        if tokens.iter().any(|token| {
+            matches!(token, Token::JumpZero(target) | Token::JumpNotZero(target)
+                if *target == 0 || *target > tokens.len())
+        }) {
+            return Err(invalid("jump target outside of the program"));
+        }
+        // Like the parser, pair every `]` with the innermost open `[`, and check both point at each
+        // other, since the interpreter's loop tracking relies on it
+        let mut open = Vec::new();
+        for (address, token) in tokens.iter().enumerate() {
+            match *token {
+                Token::JumpZero(_) => open.push(address),
+                Token::JumpNotZero(start) => match open.pop() {
+                    Some(open)
+                        if start == open + 1 && tokens[open] == Token::JumpZero(address + 1) => {}
+                    _ => return Err(invalid("jumps don't pair up like brackets")),
+                },
+                _ => (),
+            }
+        }
+        if !open.is_empty() {
+            return Err(invalid("jumps don't pair up like brackets"));
+        }
+        if units.is_empty()
+            || units
+                .iter()
+                .any(|unit| unit.start > unit.end || unit.end > tokens.len())
+        {
+            return Err(invalid("unit outside of the program"));
+        }
+
+        Ok(Program {
+            units,
+            tokens,
+            positions: Vec::new(),
+            options,
+            warnings: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Lints;
+    use std::io::BufReader;
+
+    /// A program using every kind of token and every option
+    const SOURCE: &str = "\
+;!tape-size 300
+;!fill-byte 0x2a
+;!eof set-minus-one
+;!cell-width 16
+;!expect-ptr 0x3
+;;expect:a\\x00
+;;expect-number:-7
+; first unit
++++>>-<[->+<]
+; second unit
+,.#<<<<
+";
+
+    fn parse(source: &str) -> Program {
+        Program::parse(
+            BufReader::new(source.as_bytes()),
+            true,
+            Lints::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn assert_roundtrips(program: &Program) {
+        let loaded = Program::from_bytes(&program.to_bytes()).unwrap();
+        assert_eq!(loaded.tokens, program.tokens);
+        assert_eq!(loaded.units, program.units);
+        assert_eq!(loaded.options, program.options);
+    }
+
+    fn rejection(bytes: &[u8]) -> String {
+        match Program::from_bytes(bytes) {
+            Err(ParserError::InvalidCompiled(reason)) => reason,
+            other => panic!("expected the data to be rejected, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn roundtrips_every_token_and_option() {
+        let program = parse(SOURCE);
+        for kind in [
+            "Increment",
+            "Move",
+            "JumpZero",
+            "JumpNotZero",
+            "Input",
+            "Output",
+            "PrintState",
+            "Eof",
+        ] {
+            assert!(
+                program
+                    .tokens
+                    .iter()
+                    .any(|token| format!("{token:?}").starts_with(kind)),
+                "the program has no {kind} token"
+            );
+        }
+        assert_roundtrips(&program);
+    }
+
+    #[test]
+    fn roundtrips_every_eof_behaviour_and_wide_increments() {
+        let mut program = parse(&"+".repeat(1000));
+        assert_eq!(program.tokens[0], Token::Increment(1000));
+        for eof_behaviour in [
+            EofBehaviour::SetZero,
+            EofBehaviour::SetMinusOne,
+            EofBehaviour::DontSet,
+            EofBehaviour::SetValue(0x80),
+        ] {
+            program.options.eof_behaviour = Some(eof_behaviour);
+            assert_roundtrips(&program);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = parse(SOURCE).to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(rejection(&bytes), "not a compiled brainstorm program");
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = parse(SOURCE).to_bytes();
+        bytes[MAGIC.len()] = VERSION - 1;
+        assert!(rejection(&bytes).starts_with("unsupported version"));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let bytes = parse(SOURCE).to_bytes();
+        for length in 0..bytes.len() {
+            rejection(&bytes[..length]);
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_jump_targets() {
+        for target in [0, 4, usize::MAX] {
+            let mut program = parse("[]");
+            program.tokens[0] = Token::JumpZero(target);
+            assert_eq!(
+                rejection(&program.to_bytes()),
+                "jump target outside of the program"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_jumps_that_dont_pair_up() {
+        let mut crossed = parse("[][]");
+        crossed.tokens[0] = Token::JumpZero(4);
+        let mut swapped = parse("[]");
+        swapped.tokens.swap(0, 1);
+        let mut unclosed = parse("[]+");
+        unclosed.tokens[1] = Token::Increment(1);
+        let mut nested = parse("[[]]");
+        nested.tokens[3] = Token::JumpNotZero(2);
+        for program in [crossed, swapped, unclosed, nested] {
+            assert_eq!(
+                rejection(&program.to_bytes()),
+                "jumps don't pair up like brackets"
+            );
+        }
+    }
+}
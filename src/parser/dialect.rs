@@ -0,0 +1,138 @@
+use crate::parser::ParserError;
+use clap::ValueEnum;
+use std::io::{BufRead, BufReader, Read};
+
+/// Dialects shipped with brainstorm
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum BuiltinDialect {
+    /// Ook!, where every command is a pair of `Ook.`, `Ook?` or `Ook!`
+    Ook,
+    /// Blub, which is Ook! with `Blub` instead of `Ook`
+    Blub,
+    /// Alphuck, where every command is a lowercase letter
+    Alphuck,
+}
+
+/// A mapping from a brainfuck derivative's commands to the canonical brainfuck commands.
+///
+/// Commands are matched after collapsing whitespace into single spaces, so multi-word commands
+/// (like Ook!'s) may be spread over several lines. Anything that isn't a command is a comment.
+#[derive(Debug, Clone)]
+pub struct Dialect {
+    /// Pairs of a dialect command and the brainfuck command it stands for, longest first
+    commands: Vec<(String, char)>,
+}
+
+impl Dialect {
+    fn new(mut commands: Vec<(String, char)>) -> Dialect {
+        commands.sort_by_key(|(command, _)| std::cmp::Reverse(command.len()));
+        Dialect { commands }
+    }
+
+    /// Creates one of the built-in dialects
+    pub fn builtin(dialect: BuiltinDialect) -> Dialect {
+        let ook_like = |word: &str| {
+            [
+                (". ?", '>'),
+                ("? .", '<'),
+                (". .", '+'),
+                ("! !", '-'),
+                ("! .", '.'),
+                (". !", ','),
+                ("! ?", '['),
+                ("? !", ']'),
+            ]
+            .iter()
+            .map(|(pattern, op)| {
+                let (first, second) = pattern.split_once(' ').unwrap();
+                (format!("{word}{first} {word}{second}"), *op)
+            })
+            .collect()
+        };
+
+        match dialect {
+            BuiltinDialect::Ook => Dialect::new(ook_like("Ook")),
+            BuiltinDialect::Blub => Dialect::new(ook_like("Blub")),
+            BuiltinDialect::Alphuck => Dialect::new(
+                [
+                    ('a', '>'),
+                    ('c', '<'),
+                    ('e', '+'),
+                    ('i', '-'),
+                    ('j', '.'),
+                    ('o', ','),
+                    ('p', '['),
+                    ('s', ']'),
+                ]
+                .iter()
+                .map(|(command, op)| (command.to_string(), *op))
+                .collect(),
+            ),
+        }
+    }
+
+    /// Reads a dialect from a map file. Each non-empty line holds a brainfuck command, followed by
+    /// whitespace and the dialect's command for it (e.g. `+ Ook. Ook.`).
+    pub fn from_map<T: Read>(input: BufReader<T>) -> Result<Dialect, ParserError> {
+        let mut commands = Vec::new();
+        for (line_number, line) in input.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let bad_mapping = || ParserError::BadMapping {
+                line: line_number + 1,
+                text: line.to_string(),
+            };
+            let mut chars = line.chars();
+            let op = chars
+                .next()
+                .filter(|op| "+-<>[].,#".contains(*op))
+                .ok_or_else(bad_mapping)?;
+            let command = Self::normalize(chars.as_str());
+            if command.is_empty() {
+                return Err(bad_mapping());
+            }
+            commands.push((command, op));
+        }
+        Ok(Dialect::new(commands))
+    }
+
+    /// Collapses every run of whitespace in `text` into a single space
+    fn normalize(text: &str) -> String {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Translates a line of source into canonical brainfuck commands. `carry` holds the end of the
+    /// previous line if it was the start of a command, and is updated with the end of this line.
+    pub fn translate(&self, line: &str, carry: &mut String) -> String {
+        let text = Self::normalize(&format!("{carry} {line}"));
+        carry.clear();
+
+        let mut output = String::new();
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            if let Some((command, op)) = self
+                .commands
+                .iter()
+                .find(|(command, _)| rest.starts_with(command.as_str()))
+            {
+                output.push(*op);
+                i += command.len();
+            } else if self
+                .commands
+                .iter()
+                .any(|(command, _)| command.starts_with(rest))
+            {
+                carry.push_str(rest);
+                break;
+            } else {
+                i += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        output
+    }
+}
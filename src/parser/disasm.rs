@@ -0,0 +1,120 @@
+use crate::parser::{Program, Token};
+use std::fmt::Write as _;
+
+impl Program {
+    /// Renders the program as tab-separated values, one token per line after a header line, with
+    /// the columns `pc`, `kind`, `payload`, `jump_target` and `unit_index`. Numbers are in decimal.
+    /// The payload is the signed amount of an increment or move, and the jump target is the
+    /// address of the matching bracket. Columns that don't apply to a token are left empty.
+    pub fn disasm_tsv(&self) -> String {
+        // Writing to a String cannot fail, so .unwrap() is safe to use here.
+        let mut tsv = String::from("pc\tkind\tpayload\tjump_target\tunit_index\n");
+        for (pc, token) in self.tokens.iter().enumerate() {
+            let (kind, payload, jump_target) = match *token {
+                Token::Increment(value) => ("increment", Some(value.to_string()), None),
+                Token::Move(value) => ("move", Some(value.to_string()), None),
+                Token::JumpZero(target) => ("jump-zero", None, Some(target - 1)),
+                Token::JumpNotZero(target) => ("jump-not-zero", None, Some(target - 1)),
+                Token::Input => ("input", None, None),
+                Token::Output => ("output", None, None),
+                Token::PrintState => ("print-state", None, None),
+                Token::Eof => ("eof", None, None),
+            };
+            writeln!(
+                tsv,
+                "{pc}\t{kind}\t{}\t{}\t{}",
+                payload.unwrap_or_default(),
+                jump_target
+                    .map(|target| target.to_string())
+                    .unwrap_or_default(),
+                self.unit_at(pc)
+                    .map(|unit| unit.to_string())
+                    .unwrap_or_default()
+            )
+            .unwrap();
+        }
+        tsv
+    }
+
+    /// Renders the program's tokens as plain text, one per line, leaving out jump targets so
+    /// lines only change when the tokens do
+    pub fn disassembly(&self) -> Vec<String> {
+        self.tokens
+            .iter()
+            .filter(|token| **token != Token::Eof)
+            .map(|token| token.to_string())
+            .collect()
+    }
+
+    /// Renders a unified diff of the disassembly of two programs. Each line holds a marker (`-`
+    /// for tokens only in this program, `+` for tokens only in `other`), the token's address in
+    /// each program and the token. Unchanged tokens are only shown around changes, and skipped
+    /// runs of them are marked with `...`. Returns an empty string if the programs have the same
+    /// tokens.
+    pub fn diff_disassembly(&self, other: &Program) -> String {
+        /// How many unchanged tokens are shown before and after each change
+        const CONTEXT: usize = 3;
+
+        let (a, b) = (self.disassembly(), other.disassembly());
+
+        // lengths[i][j] holds the length of the longest common subsequence of a[i..] and b[j..]
+        let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i][j] = if a[i] == b[j] {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        let mut lines = Vec::new();
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                lines.push((' ', Some(i), Some(j), &a[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if j == b.len() || (i < a.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+                lines.push(('-', Some(i), None, &a[i]));
+                i += 1;
+            } else {
+                lines.push(('+', None, Some(j), &b[j]));
+                j += 1;
+            }
+        }
+
+        let changes: Vec<usize> = (0..lines.len())
+            .filter(|&index| lines[index].0 != ' ')
+            .collect();
+        let shown = |index: usize| {
+            changes
+                .iter()
+                .any(|&change| change.abs_diff(index) <= CONTEXT)
+        };
+        let width = a.len().max(b.len()).max(1).ilog(16) as usize + 3;
+        let address = |address: Option<usize>| match address {
+            Some(address) => format!("{:<width$}", format!("{address:#x}")),
+            None => " ".repeat(width),
+        };
+
+        // Writing to a String cannot fail, so .unwrap() is safe to use here.
+        let mut diff = String::new();
+        let mut skipped = false;
+        for (index, (marker, a, b, token)) in lines.iter().enumerate() {
+            if !shown(index) {
+                skipped = true;
+                continue;
+            }
+            if skipped && !diff.is_empty() {
+                writeln!(diff, "...").unwrap();
+            }
+            skipped = false;
+            writeln!(diff, "{marker} {}  {}  {token}", address(*a), address(*b)).unwrap();
+        }
+        if skipped && !diff.is_empty() {
+            writeln!(diff, "...").unwrap();
+        }
+        diff
+    }
+}
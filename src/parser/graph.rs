@@ -0,0 +1,50 @@
+use crate::parser::{Program, Token};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+impl Program {
+    /// Returns the index of the unit an address belongs to
+    pub fn unit_at(&self, address: usize) -> Option<usize> {
+        self.units
+            .iter()
+            .position(|unit| (unit.start..unit.end).contains(&address))
+    }
+
+    /// Renders the control flow between units as a graph in the DOT language. Each unit is a node,
+    /// and there's an edge for every pair of units where a jump in one targets the other, labeled
+    /// with the amount of such jumps. Falling through from one unit into the next is drawn dashed.
+    pub fn unit_graph_dot(&self) -> String {
+        let mut jumps = BTreeMap::new();
+        for (address, token) in self.tokens.iter().enumerate() {
+            if let Token::JumpZero(target) | Token::JumpNotZero(target) = *token
+                && let (Some(from), Some(to)) = (self.unit_at(address), self.unit_at(target - 1))
+                && from != to
+            {
+                *jumps.entry((from, to)).or_insert(0) += 1;
+            }
+        }
+
+        // Writing to a String cannot fail, so .unwrap() is safe to use here.
+        let mut dot = String::new();
+        writeln!(dot, "digraph units {{").unwrap();
+        writeln!(dot, "    node [shape=box];").unwrap();
+        for (index, unit) in self.units.iter().enumerate() {
+            writeln!(
+                dot,
+                "    unit{index} [label=\"{}\\n{:#x}..{:#x}\"];",
+                unit.description.replace('\\', "\\\\").replace('"', "\\\""),
+                unit.start,
+                unit.end
+            )
+            .unwrap();
+        }
+        for index in 1..self.units.len() {
+            writeln!(dot, "    unit{} -> unit{index} [style=dashed];", index - 1).unwrap();
+        }
+        for ((from, to), count) in jumps {
+            writeln!(dot, "    unit{from} -> unit{to} [label=\"{count}\"];").unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
@@ -0,0 +1,73 @@
+use crate::parser::{Program, Token};
+
+/// A well-known program that `Program::identify` can recognize
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KnownProgram {
+    pub name: &'static str,
+    /// Where the canonical version of the program comes from
+    pub origin: &'static str,
+    /// The program's `Program::token_hash`
+    pub hash: u64,
+}
+
+/// Programs recognized by `Program::identify`, by the hash of their normalized tokens
+static KNOWN_PROGRAMS: &[KnownProgram] = &[
+    KnownProgram {
+        name: "Hello World!",
+        origin: "the Wikipedia article on brainfuck",
+        hash: 0xc72d90eb64e3e233,
+    },
+    KnownProgram {
+        name: "Hello, World!",
+        origin: "the shortest known version, which needs a tape that wraps around",
+        hash: 0x412527d2a35d9bab,
+    },
+    KnownProgram {
+        name: "cat",
+        origin: "the classic `,[.,]`",
+        hash: 0x067130473962954d,
+    },
+    KnownProgram {
+        name: "Mandelbrot set viewer",
+        origin: "Erik Bosman's benchmark program",
+        hash: 0xa636f9e4c97d7898,
+    },
+];
+
+impl Program {
+    /// Returns a 64-bit FNV-1a hash of the program's normalized tokens, which only depends on what
+    /// the program does: comments, formatting, units and `#` are ignored, adjacent runs of
+    /// increments or moves are merged, and runs that cancel out are dropped.
+    pub fn token_hash(&self) -> u64 {
+        let mut normalized: Vec<Token> = Vec::new();
+        for token in &self.tokens {
+            match (normalized.last_mut(), *token) {
+                (_, Token::PrintState | Token::Eof) => continue,
+                (Some(Token::Increment(previous)), Token::Increment(value)) => {
+                    *previous = previous.wrapping_add(value)
+                }
+                (Some(Token::Move(previous)), Token::Move(value)) => {
+                    *previous = previous.wrapping_add(value)
+                }
+                (_, token) => normalized.push(token),
+            }
+            if let Some(Token::Increment(0) | Token::Move(0)) = normalized.last() {
+                normalized.pop();
+            }
+        }
+
+        // Jump targets depend on the merging above, so tokens are hashed as displayed instead
+        normalized
+            .iter()
+            .flat_map(|token| token.to_string().into_bytes())
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Returns the well-known program this program is a copy of, if any
+    pub fn identify(&self) -> Option<&'static KnownProgram> {
+        let hash = self.token_hash();
+        KNOWN_PROGRAMS.iter().find(|known| known.hash == hash)
+    }
+}
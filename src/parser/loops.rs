@@ -0,0 +1,62 @@
+use crate::parser::{Program, Token};
+use std::collections::BTreeMap;
+
+/// The net effect of a simple loop: one whose body only adds to cells and moves, ending where it
+/// started, and which steps the cell it tests by exactly one on each iteration. Such a loop runs
+/// a number of times given by that cell, and so adds a multiple of it to every other cell it
+/// touches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopEffect {
+    /// The address of the loop's `[`
+    pub start: usize,
+    /// The address of the loop's `]`
+    pub end: usize,
+    /// How much each iteration adds to each cell the body touches, by its offset from the cell the
+    /// loop tests. The tested cell itself is at offset 0, and is changed by either 1 or -1.
+    pub deltas: BTreeMap<isize, i32>,
+}
+
+impl LoopEffect {
+    /// Returns the multiple of the tested cell's initial value that the whole loop adds to every
+    /// other cell it touches, by offset. The tested cell always ends at zero.
+    pub fn multipliers(&self) -> Vec<(isize, i32)> {
+        // A loop stepping its cell down runs `cell` times, and one stepping it up `-cell` times
+        let direction = self.deltas[&0].wrapping_neg();
+        self.deltas
+            .iter()
+            .filter(|&(&offset, &delta)| offset != 0 && delta != 0)
+            .map(|(&offset, &delta)| (offset, delta.wrapping_mul(direction)))
+            .collect()
+    }
+}
+
+impl Program {
+    /// Analyzes the loop whose `[` is at `start`, returning its net effect if it's a simple loop,
+    /// as described in `LoopEffect`. Loops with I/O, nested loops or a body that moves the pointer
+    /// overall aren't simple.
+    pub fn simple_loop_effect(&self, start: usize) -> Option<LoopEffect> {
+        let Some(Token::JumpZero(target)) = self.tokens.get(start) else {
+            return None;
+        };
+        let end = target - 1;
+
+        let mut deltas = BTreeMap::new();
+        let mut offset = 0isize;
+        for token in &self.tokens[start + 1..end] {
+            match token {
+                Token::Increment(value) => {
+                    let delta = deltas.entry(offset).or_insert(0i32);
+                    *delta = delta.wrapping_add(*value);
+                }
+                Token::Move(value) => offset = offset.wrapping_add(*value),
+                _ => return None,
+            }
+        }
+
+        (offset == 0 && matches!(deltas.get(&0), Some(1 | -1))).then_some(LoopEffect {
+            start,
+            end,
+            deltas,
+        })
+    }
+}
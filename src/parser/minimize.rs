@@ -0,0 +1,56 @@
+use crate::parser::ast::Node;
+use crate::parser::{Program, Token};
+
+impl Program {
+    /// Returns a smaller program that produces the same output for any input, by conservatively
+    /// removing dead code:
+    /// - loops that can never be entered, because they start right after another loop (which
+    ///   leaves the cell at zero), or at the very start of a program whose tape is filled with zero
+    /// - operations after the last output, as long as they aren't loops (which might never end)
+    /// - increments and moves that cancel out, after merging runs that became adjacent
+    ///
+    /// `fill_byte` is the value the tape will be filled with. The result has a single unit.
+    pub fn minimize(&self, fill_byte: u8) -> Program {
+        let mut ast = self.to_ast();
+        if fill_byte == 0 {
+            // The first cell is zero at the start, so leading loops are skipped
+            let leading = ast
+                .iter()
+                .take_while(|node| matches!(node, Node::Loop(_)))
+                .count();
+            ast.drain(..leading);
+        }
+        let mut ast = Self::minimize_nodes(ast);
+
+        let last_output = ast.iter().rposition(|node| match node {
+            Node::Op(token) => matches!(token, Token::Output | Token::PrintState),
+            Node::Loop(_) => true,
+        });
+        ast.truncate(last_output.map_or(0, |last| last + 1));
+
+        Program::from_ast(&ast)
+    }
+
+    /// Removes loops that directly follow another loop, and merges adjacent increments and moves,
+    /// dropping those that end up doing nothing
+    fn minimize_nodes(nodes: Vec<Node>) -> Vec<Node> {
+        let mut minimized: Vec<Node> = Vec::new();
+        for node in nodes {
+            match (minimized.last_mut(), node) {
+                (Some(Node::Loop(_)), Node::Loop(_)) => (),
+                (_, Node::Loop(body)) => minimized.push(Node::Loop(Self::minimize_nodes(body))),
+                (Some(Node::Op(Token::Increment(previous))), Node::Op(Token::Increment(value))) => {
+                    *previous = previous.wrapping_add(value)
+                }
+                (Some(Node::Op(Token::Move(previous))), Node::Op(Token::Move(value))) => {
+                    *previous = previous.wrapping_add(value)
+                }
+                (_, node) => minimized.push(node),
+            }
+            if let Some(Node::Op(Token::Increment(0) | Token::Move(0))) = minimized.last() {
+                minimized.pop();
+            }
+        }
+        minimized
+    }
+}
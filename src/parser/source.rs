@@ -0,0 +1,290 @@
+use crate::interpreter::{EofBehaviour, Interpreter};
+use crate::parser::{Lints, Program, Token};
+use std::io::BufReader;
+
+/// Descriptions the parser gives to units it creates for code that isn't in a named unit
+const DEFAULT_UNIT_NAMES: [&str; 2] = ["No Unit Name", "No Unit Information"];
+/// The tape size `Program::assert_roundtrip` runs programs that don't declare one with
+const ROUNDTRIP_TAPE_SIZE: usize = 1024 * 64;
+/// Steps after which `Program::assert_roundtrip` assumes a program loops forever
+const ROUNDTRIP_MAX_STEPS: u64 = 10_000_000;
+
+/// Returns a unit's description as it's emitted on its `;` line. The parser also runs the commands
+/// on that line, so they're left out to keep them from changing the program.
+fn unit_header(description: &str) -> String {
+    description
+        .chars()
+        .filter(|char| !"+-<>[].,#".contains(*char))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+impl Program {
+    /// Emits brainfuck source equivalent to the program, with each named unit on its own line
+    /// preceded by a `;` line holding its description, without any commands in it. Coalesced
+    /// tokens are expanded back into runs of single commands.
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        for (index, unit) in self.units.iter().enumerate() {
+            if !(index == 0 && DEFAULT_UNIT_NAMES.contains(&unit.description.as_str())) {
+                source.push_str("; ");
+                source.push_str(&unit_header(&unit.description));
+                source.push('\n');
+            }
+
+            for token in &self.tokens[unit.start..unit.end] {
+                source.push_str(&Self::token_source(*token));
+            }
+            source.push('\n');
+        }
+        source
+    }
+
+    /// Emits the program as formatted brainfuck source: directives for the program's options come
+    /// first, loops are indented by nesting depth and each named unit starts with its `;`
+    /// description as a section header.
+    pub fn to_formatted_source(&self) -> String {
+        const INDENT: &str = "    ";
+
+        let mut source = String::new();
+        if let Some(tape_size) = self.options.tape_size {
+            source.push_str(&format!(";!tape-size {tape_size}\n"));
+        }
+        if let Some(fill_byte) = self.options.fill_byte {
+            source.push_str(&format!(";!fill-byte {fill_byte:#04x}\n"));
+        }
+        if let Some(eof_behaviour) = self.options.eof_behaviour {
+            source.push_str(&format!(";!eof {eof_behaviour}\n"));
+        }
+        if let Some(cell_width) = self.options.cell_width {
+            source.push_str(&format!(";!cell-width {cell_width}\n"));
+        }
+        if let Some(expect_ptr) = self.options.expect_ptr {
+            source.push_str(&format!(";!expect-ptr {expect_ptr:#x}\n"));
+        }
+        if let Some(expect_output) = &self.options.expect_output {
+            source.push_str(&format!(";;expect:{}\n", expect_output.escape_ascii()));
+        }
+        if let Some(expect_number) = self.options.expect_number {
+            source.push_str(&format!(";;expect-number:{expect_number}\n"));
+        }
+
+        let mut depth = 0;
+        let mut line = String::new();
+        let flush = |source: &mut String, line: &mut String, depth: usize| {
+            if !line.is_empty() {
+                source.push_str(&INDENT.repeat(depth));
+                source.push_str(line);
+                source.push('\n');
+                line.clear();
+            }
+        };
+
+        for (index, unit) in self.units.iter().enumerate() {
+            flush(&mut source, &mut line, depth);
+            if !(index == 0 && DEFAULT_UNIT_NAMES.contains(&unit.description.as_str())) {
+                if !source.is_empty() && !source.ends_with("[\n") {
+                    source.push('\n');
+                }
+                source.push_str(&format!(
+                    "{}; {}\n",
+                    INDENT.repeat(depth),
+                    unit_header(&unit.description)
+                ));
+            }
+
+            for token in &self.tokens[unit.start..unit.end] {
+                match token {
+                    Token::JumpZero(_) => {
+                        flush(&mut source, &mut line, depth);
+                        source.push_str(&format!("{}[\n", INDENT.repeat(depth)));
+                        depth += 1;
+                    }
+                    Token::JumpNotZero(_) => {
+                        flush(&mut source, &mut line, depth);
+                        depth -= 1;
+                        source.push_str(&format!("{}]\n", INDENT.repeat(depth)));
+                    }
+                    Token::Eof => (),
+                    token => {
+                        if !line.is_empty() {
+                            line.push(' ');
+                        }
+                        line.push_str(&Self::token_source(*token));
+                    }
+                }
+            }
+        }
+        flush(&mut source, &mut line, depth);
+        source
+    }
+
+    /// Emits the shortest brainfuck source equivalent to the program: only commands, without any
+    /// whitespace, units, directives or `#`. Coalesced tokens are expanded back into runs of
+    /// single commands. Increments aren't shortened by wrapping around the other way, since where
+    /// cells wrap depends on the cell width.
+    pub fn to_minified_source(&self) -> String {
+        self.tokens
+            .iter()
+            .filter(|token| **token != Token::PrintState)
+            .map(|token| Self::token_source(*token))
+            .collect()
+    }
+
+    /// Returns the brainfuck commands a token was parsed from
+    fn token_source(token: Token) -> String {
+        match token {
+            Token::Increment(value) => {
+                let command = if value > 0 { "+" } else { "-" };
+                command.repeat(value.unsigned_abs() as usize)
+            }
+            Token::Move(value) => {
+                let command = if value > 0 { ">" } else { "<" };
+                command.repeat(value.unsigned_abs())
+            }
+            Token::JumpZero(_) => "[".to_string(),
+            Token::JumpNotZero(_) => "]".to_string(),
+            Token::Input => ",".to_string(),
+            Token::Output => ".".to_string(),
+            Token::PrintState => "#".to_string(),
+            Token::Eof => String::new(),
+        }
+    }
+
+    /// Asserts that `Program::to_source` round-trips: re-parsing its output must yield exactly
+    /// the same tokens and units (whose descriptions lose any commands), and running both programs
+    /// on each of `inputs` must end the same way, with the same output, pointer and tape. Runs use
+    /// the program's own options and give up after `ROUNDTRIP_MAX_STEPS` steps, and `#` is skipped
+    /// so the check stays quiet.
+    ///
+    /// # Panics
+    ///
+    /// Panics, describing the first difference, if the program doesn't round-trip.
+    pub fn assert_roundtrip(&self, inputs: &[&[u8]]) {
+        let source = self.to_source();
+        let mut reparsed = Program::parse(
+            BufReader::new(source.as_bytes()),
+            true,
+            Lints::default(),
+            None,
+        )
+        .unwrap_or_else(|e| panic!("emitted source failed to parse: {e}\n{source}"));
+
+        if let Some(i) = (0..self.tokens.len().max(reparsed.tokens.len()))
+            .find(|&i| self.tokens.get(i) != reparsed.tokens.get(i))
+        {
+            panic!(
+                "emitted source differs at token {i:#x}: expected {:?}, got {:?}\n{source}",
+                self.tokens.get(i),
+                reparsed.tokens.get(i)
+            );
+        }
+        let expected_units: Vec<_> = self
+            .units
+            .iter()
+            .map(|unit| (unit_header(&unit.description), unit.start, unit.end))
+            .collect();
+        let units: Vec<_> = reparsed
+            .units
+            .iter()
+            .map(|unit| (unit.description.clone(), unit.start, unit.end))
+            .collect();
+        assert_eq!(
+            expected_units, units,
+            "emitted source has different units\n{source}"
+        );
+
+        // The emitted source has no directives, so both programs run with the original's options
+        reparsed.options = self.options.clone();
+        for input in inputs {
+            let (result, output, ptr, tape) = self.roundtrip_run(input);
+            let (reparsed_result, reparsed_output, reparsed_ptr, reparsed_tape) =
+                reparsed.roundtrip_run(input);
+            let input = input.escape_ascii();
+            assert_eq!(
+                result, reparsed_result,
+                "emitted source ends differently on input \"{input}\"\n{source}"
+            );
+            assert_eq!(
+                output.escape_ascii().to_string(),
+                reparsed_output.escape_ascii().to_string(),
+                "emitted source outputs differently on input \"{input}\"\n{source}"
+            );
+            assert_eq!(
+                ptr, reparsed_ptr,
+                "emitted source ends at a different pointer on input \"{input}\"\n{source}"
+            );
+            if let Some(i) = (0..tape.len()).find(|&i| tape[i] != reparsed_tape[i]) {
+                panic!(
+                    "emitted source leaves cell {i:#x} at {:#x} instead of {:#x} on input \"{input}\"\n{source}",
+                    reparsed_tape[i], tape[i]
+                );
+            }
+        }
+    }
+
+    /// Runs the program on `input` for `assert_roundtrip`, returning how the run ended, its
+    /// output, and the final pointer and tape
+    fn roundtrip_run(&self, input: &[u8]) -> (String, Vec<u8>, usize, Vec<u32>) {
+        let mut program = self.clone();
+        // A no-op in place of every `#` keeps the addresses of the rest of the program
+        for token in &mut program.tokens {
+            if *token == Token::PrintState {
+                *token = Token::Move(0);
+            }
+        }
+
+        let mut interpreter = Interpreter::new(
+            program,
+            self.options.tape_size.unwrap_or(ROUNDTRIP_TAPE_SIZE),
+            self.options.fill_byte.unwrap_or(0),
+            self.options.eof_behaviour.unwrap_or(EofBehaviour::DontSet),
+            input,
+            Vec::new(),
+        );
+        interpreter.set_cell_width(self.options.cell_width.unwrap_or_default());
+        let result = match interpreter.cont_for(ROUNDTRIP_MAX_STEPS) {
+            Ok(Some(_)) => "halted".to_string(),
+            Ok(None) => format!("gave up after {ROUNDTRIP_MAX_STEPS} steps"),
+            Err(e) => e.to_string(),
+        };
+        (
+            result,
+            interpreter.output().clone(),
+            interpreter.ptr(),
+            interpreter.tape().to_vec(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Lints, Program};
+    use std::io::BufReader;
+
+    fn parse(source: &str) -> Program {
+        Program::parse(
+            BufReader::new(source.as_bytes()),
+            true,
+            Lints::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn to_source_roundtrips() {
+        let program = parse(
+            ";!eof set-zero\n; read\n,[>+++<-]\n; shift back\n>>>>>><<<[-]+.\n; echo\n,[.,]#\n",
+        );
+        program.assert_roundtrip(&[b"", b"a", b"hello\n"]);
+    }
+
+    #[test]
+    fn to_source_leaves_commands_out_of_unit_names() {
+        let program = parse("; add 1 + 1, print.\n++.\n");
+        assert_eq!(program.to_source(), "; add 1  1 print\n+,.++.\n");
+        program.assert_roundtrip(&[b""]);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::parser::{Program, Token, Unit};
+
+impl Program {
+    /// Splits a program without units of its own into inferred ones, so unit-based navigation is
+    /// useful for it too. Every loop at the top level (not nested in another loop) becomes a unit
+    /// named `loop@<address>`, and the code between such loops becomes a unit named
+    /// `code@<address>`. Programs with units from `;` lines keep them, as those are assumed to be
+    /// better than any guess. Returns whether units were inferred.
+    pub fn infer_units(&mut self) -> bool {
+        if self.units.len() != 1 || self.units[0].description != "No Unit Information" {
+            return false;
+        }
+
+        let mut units: Vec<Unit> = Vec::new();
+        let mut depth = 0;
+        for (address, token) in self.tokens.iter().enumerate() {
+            let is_loop = match token {
+                Token::JumpZero(_) => {
+                    depth += 1;
+                    depth == 1
+                }
+                Token::JumpNotZero(_) => {
+                    depth -= 1;
+                    depth == 0
+                }
+                _ => depth > 0,
+            };
+            let kind = if is_loop { "loop" } else { "code" };
+            match units.last_mut() {
+                // A top-level loop always starts a unit, even right after another one
+                Some(unit)
+                    if unit.description.starts_with(kind)
+                        && !(depth == 1 && matches!(token, Token::JumpZero(_))) =>
+                {
+                    unit.end = address + 1
+                }
+                _ => units.push(Unit {
+                    description: format!("{kind}@{address:#x}"),
+                    start: address,
+                    end: address + 1,
+                }),
+            }
+        }
+
+        self.units = units;
+        true
+    }
+}